@@ -0,0 +1,200 @@
+use green::{escape_dot, GreenRelations};
+use regex::word_to_string;
+use std::collections::BTreeMap;
+use syntactic_monoid::{ElemType, SyntacticMonoid};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Right, // e -> e * a
+    Left,  // e -> a * e
+}
+
+fn edge(monoid: &SyntacticMonoid, side: Side, e: ElemType, a: u8) -> ElemType {
+    match side {
+        Side::Right => monoid.right_cayley(e, a).unwrap(),
+        Side::Left => monoid.left_cayley(e, a).unwrap(),
+    }
+}
+
+// The Cayley graph of the monoid with respect to the letters. With `collapse`,
+// each strongly connected component becomes one node: these are the R-classes
+// of the right graph and the L-classes of the left graph.
+pub struct CayleyGraph {
+    pub side: Side,
+    pub collapsed: bool,
+    // the elements of each node, a single one unless collapsed
+    pub nodes: Vec<Vec<ElemType>>,
+    // (from, to, letters); collapsed graphs merge parallel edges and drop loops
+    pub edges: Vec<(usize, usize, Vec<u8>)>,
+}
+
+impl CayleyGraph {
+    pub fn new(monoid: &SyntacticMonoid, side: Side, collapse: bool) -> Self {
+        let letters: Vec<u8> = monoid.alphabet().iter().map(|a| a as u8).collect();
+        if !collapse {
+            let mut edges = Vec::new();
+            for e in 0..monoid.size() {
+                for a in &letters {
+                    edges.push((e, edge(monoid, side, e, *a), vec![*a]));
+                }
+            }
+            return CayleyGraph {
+                side,
+                collapsed: false,
+                nodes: (0..monoid.size()).map(|e| vec![e]).collect(),
+                edges,
+            };
+        }
+
+        let green = GreenRelations::new(monoid);
+        let nodes = match side {
+            Side::Right => green.r_classes.clone(),
+            Side::Left => green.l_classes.clone(),
+        };
+        let class_of = |e| match side {
+            Side::Right => green.r_class_of(e),
+            Side::Left => green.l_class_of(e),
+        };
+        // edges between components, with the letters of parallel edges merged
+        let mut edges: BTreeMap<(usize, usize), Vec<u8>> = BTreeMap::new();
+        for e in 0..monoid.size() {
+            for a in &letters {
+                let (p, q) = (class_of(e), class_of(edge(monoid, side, e, *a)));
+                if p == q {
+                    continue;
+                }
+                let labels = edges.entry((p, q)).or_default();
+                if !labels.contains(a) {
+                    labels.push(*a);
+                }
+            }
+        }
+        CayleyGraph {
+            side,
+            collapsed: true,
+            nodes,
+            edges: edges
+                .into_iter()
+                .map(|((p, q), labels)| (p, q, labels))
+                .collect(),
+        }
+    }
+
+    pub fn to_graphviz(&self, monoid: &SyntacticMonoid) {
+        println!(
+            "digraph {} {{",
+            match self.side {
+                Side::Right => "RightCayley",
+                Side::Left => "LeftCayley",
+            }
+        );
+        println!("  rankdir=\"LR\"");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let words: Vec<String> = node.iter().map(|e| monoid.label(*e)).collect();
+            let label = escape_dot(&words.join(" "));
+            if self.collapsed {
+                println!("  {} [ shape=box, label = \"{}\" ];", i, label);
+            } else {
+                println!("  {} [ label = \"{}\" ];", i, label);
+            }
+        }
+        for &(p, q, ref letters) in &self.edges {
+            let labels: Vec<String> = letters.iter().map(|a| word_to_string(&[*a])).collect();
+            println!(
+                " {} -> {} [ label = \"{}\" ];",
+                p,
+                q,
+                escape_dot(&labels.join(","))
+            );
+        }
+        println!("}}");
+    }
+}
+
+pub fn to_graphviz(monoid: &SyntacticMonoid, side: Side, collapse: bool) {
+    CayleyGraph::new(monoid, side, collapse).to_graphviz(monoid);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntactic_monoid::tests::monoid;
+
+    // reach[e][f] iff f is reachable from e in the graph
+    fn reachability(graph: &CayleyGraph) -> Vec<Vec<bool>> {
+        let n = graph.nodes.len();
+        let mut reach: Vec<Vec<bool>> = (0..n).map(|e| (0..n).map(|f| e == f).collect()).collect();
+        for &(p, q, _) in &graph.edges {
+            reach[p][q] = true;
+        }
+        for k in 0..n {
+            for i in 0..n {
+                for j in 0..n {
+                    reach[i][j] = reach[i][j] || (reach[i][k] && reach[k][j]);
+                }
+            }
+        }
+        reach
+    }
+
+    #[test]
+    fn edges_are_products_with_letters() {
+        for &(pattern, letters) in &[("(a|ba)*", "ab"), ("(ab)*", "ab"), ("a*b", "ab")] {
+            let sm = monoid(pattern, letters);
+            for &side in &[Side::Right, Side::Left] {
+                let graph = CayleyGraph::new(&sm, side, false);
+                assert_eq!(
+                    graph.nodes,
+                    (0..sm.size()).map(|e| vec![e]).collect::<Vec<_>>()
+                );
+                assert_eq!(graph.edges.len(), sm.size() * letters.len());
+                for &(e, f, ref a) in &graph.edges {
+                    let a = sm.letter(a[0]).unwrap();
+                    let product = match side {
+                        Side::Right => sm.multiply(e, a),
+                        Side::Left => sm.multiply(a, e),
+                    };
+                    assert_eq!(f, product, "{}", pattern);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn collapsed_nodes_are_green_classes() {
+        for &(pattern, letters) in &[("(a|ba)*", "ab"), ("(ab)*", "ab"), ("a*b", "ab")] {
+            let sm = monoid(pattern, letters);
+            let green = GreenRelations::new(&sm);
+            for &side in &[Side::Right, Side::Left] {
+                let graph = CayleyGraph::new(&sm, side, true);
+                let classes = match side {
+                    Side::Right => &green.r_classes,
+                    Side::Left => &green.l_classes,
+                };
+                assert_eq!(&graph.nodes, classes);
+                // and they are the strongly connected components of the full graph
+                let reach = reachability(&CayleyGraph::new(&sm, side, false));
+                for node in &graph.nodes {
+                    for &e in node {
+                        for (f, &forward) in reach[e].iter().enumerate() {
+                            assert_eq!(node.contains(&f), forward && reach[f][e]);
+                        }
+                    }
+                }
+                for &(p, q, _) in &graph.edges {
+                    assert_ne!(p, q);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parallel_edges_merge() {
+        // in (a|b)*a the right graph sends 1 to a by a and to b by b, and a and b
+        // are one R-class, so the collapsed graph has a single edge labelled a,b
+        let sm = monoid("(a|b)*a", "ab");
+        let graph = CayleyGraph::new(&sm, Side::Right, true);
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.edges, vec![(0, 1, b"ab".to_vec())]);
+    }
+}
@@ -1,8 +1,10 @@
-use nfa::NFA;
+use bit_set::BitSet;
 use nfa::SubSet;
-use std::collections::HashMap;
+use nfa::NFA;
+use regex::full_alphabet;
 use std::collections::BTreeSet;
-use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::VecDeque;
 
 #[derive(Debug, Clone)]
@@ -16,12 +18,12 @@ impl State {
     pub fn new(id: i32, accept: bool) -> Self {
         State {
             t: vec![-1; 256],
-            id: id,
-            accept: accept,
+            id,
+            accept,
         }
     }
-    pub fn set_trans(&mut self, transition: &Vec<i32>) {
-        self.t = transition.clone();
+    pub fn set_trans(&mut self, transition: &[i32]) {
+        self.t = transition.to_vec();
     }
     pub fn transition(&self, c: u8) -> i32 {
         self.t[c as usize]
@@ -30,14 +32,12 @@ impl State {
         self.t[ch] = id;
     }
     pub fn print_trans(&self) {
-        let mut i = 0;
-        for t in &self.t {
+        for (i, t) in self.t.iter().enumerate() {
             if *t != -1 {
                 print!("{} by {} ", *t, i as u8 as char);
             }
-            i = i + 1;
         }
-        println!("");
+        println!();
     }
 }
 
@@ -45,14 +45,22 @@ impl State {
 pub struct DFA {
     pub states: Vec<State>,
     pub start: usize,
+    pub alphabet: BitSet,
     is_minimum: bool,
 }
 
+impl Default for DFA {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DFA {
     pub fn new() -> Self {
         DFA {
             states: Vec::new(),
             start: 0,
+            alphabet: full_alphabet(),
             is_minimum: false,
         }
     }
@@ -60,6 +68,10 @@ impl DFA {
         self.states.len()
     }
 
+    pub fn is_minimum(&self) -> bool {
+        self.is_minimum
+    }
+
     pub fn get_trans(&self, i: usize, c: u8) -> i32 {
         self.states[i].transition(c)
     }
@@ -72,7 +84,7 @@ impl DFA {
         for s in subset {
             print!("{} ", s);
         }
-        println!("");
+        println!();
     }
     pub fn construct_from_nfa(nfa: &NFA) -> Self {
         let mut family: BTreeSet<SubSet> = BTreeSet::new();
@@ -91,7 +103,7 @@ impl DFA {
                 continue;
             }
             family.insert(subset.clone());
-            for ch in 0..256 {
+            for ch in nfa.alphabet.iter() {
                 let mut next = SubSet::new();
                 for s in &subset {
                     for q in nfa.states[*s].ts[ch].iter() {
@@ -109,10 +121,11 @@ impl DFA {
         }
 
         let mut dfa = DFA::new();
+        dfa.alphabet = nfa.alphabet.clone();
 
         for (dfa_id, subset) in family.iter().enumerate() {
             let mut dfa_state = State::new(dfa_id as i32, false);
-            for ch in 0..256 {
+            for ch in nfa.alphabet.iter() {
                 let mut trans_subset = SubSet::new();
                 for s in subset {
                     for q in nfa.states[*s].ts[ch].iter() {
@@ -123,7 +136,7 @@ impl DFA {
                     continue;
                 }
                 let trans_subset = nfa.epsilon_expand(&trans_subset);
-                dfa_state.t[ch as usize] = *nfa2dfa_id.get(&trans_subset).unwrap() as i32;
+                dfa_state.t[ch] = *nfa2dfa_id.get(&trans_subset).unwrap() as i32;
             }
             for s in subset {
                 if nfa.states[*s].id == nfa.start.id {
@@ -138,64 +151,283 @@ impl DFA {
         dfa
     }
 
-    pub fn minimize(&self) -> Self {
-        let mut min_dfa = self.reduction();
-        loop {
-            let next_dfa = min_dfa.reduction();
-            if min_dfa.size() == next_dfa.size() {
-                break;
+    // adds a sink state so that every transition on the alphabet is defined
+    fn complete(&self) -> Self {
+        let sink = self.size() as i32;
+        let mut dfa = self.clone();
+        let mut needs_sink = dfa.states.is_empty();
+        for s in &mut dfa.states {
+            for ch in self.alphabet.iter() {
+                if s.t[ch] == -1 {
+                    s.t[ch] = sink;
+                    needs_sink = true;
+                }
             }
-            min_dfa = next_dfa;
         }
-        min_dfa
+        if needs_sink {
+            let mut s = State::new(sink, false);
+            for ch in self.alphabet.iter() {
+                s.add_trans(sink, ch);
+            }
+            dfa.add_state(s);
+            dfa.is_minimum = false;
+        }
+        dfa
     }
 
-    pub fn reduction(&self) -> Self {
-        // 遷移関数が同じ && 受理状態が同じ 状態を同一視する
-        let mut transition_map: BTreeMap<(Vec<i32>, bool), Vec<i32>> = BTreeMap::new();
-        for s in &self.states {
-            transition_map.insert((s.t.clone(), s.accept), Vec::new());
+    pub fn complement(&self) -> Self {
+        let mut dfa = self.complete();
+        for s in &mut dfa.states {
+            s.accept = !s.accept;
         }
+        dfa
+    }
 
-        for s in &self.states {
-            if let Some(mut v) = transition_map.get_mut(&(s.t.clone(), s.accept)) {
-                v.push(s.id);
+    pub fn intersect(&self, other: &DFA) -> Self {
+        self.product(other, |p, q| p && q)
+    }
+
+    pub fn union(&self, other: &DFA) -> Self {
+        self.product(other, |p, q| p || q)
+    }
+
+    pub fn difference(&self, other: &DFA) -> Self {
+        self.product(other, |p, q| p && !q)
+    }
+
+    pub fn symmetric_difference(&self, other: &DFA) -> Self {
+        self.product(other, |p, q| p != q)
+    }
+
+    // product automaton over the union of both alphabets; a missing transition
+    // on either side moves that component to the implicit sink (-1).
+    // `op(false, false)` must be false, so the pair of sinks stays implicit.
+    fn product(&self, other: &DFA, op: fn(bool, bool) -> bool) -> Self {
+        debug_assert!(!op(false, false));
+        let mut pair2id: HashMap<(i32, i32), usize> = HashMap::new();
+        let mut queue: VecDeque<(i32, i32)> = VecDeque::new();
+        let mut dfa = DFA::new();
+        dfa.alphabet = self.alphabet.clone();
+        dfa.alphabet.union_with(&other.alphabet);
+        let start = (self.start_or_sink(), other.start_or_sink());
+        pair2id.insert(start, 0);
+        queue.push_back(start);
+        while let Some((p, q)) = queue.pop_front() {
+            let id = pair2id[&(p, q)];
+            let mut state = State::new(id as i32, op(self.accepts_at(p), other.accepts_at(q)));
+            for ch in dfa.alphabet.iter() {
+                let next = (
+                    self.next_or_sink(p, ch as u8),
+                    other.next_or_sink(q, ch as u8),
+                );
+                if next == (-1, -1) {
+                    continue;
+                }
+                let next_id = pair2id.len();
+                let next_id = *pair2id.entry(next).or_insert_with(|| {
+                    queue.push_back(next);
+                    next_id
+                });
+                state.add_trans(next_id as i32, ch);
             }
+            dfa.add_state(state);
         }
+        dfa
+    }
+
+    // the start state, or the implicit sink (-1) if there are no states
+    fn start_or_sink(&self) -> i32 {
+        if self.states.is_empty() {
+            -1
+        } else {
+            self.start as i32
+        }
+    }
 
-        let mut min_dfa_ids: Vec<i32> = vec![-1; self.size()];
-        for (min_id, m) in transition_map.iter().enumerate() {
-            for id in m.1 {
-                min_dfa_ids[*id as usize] = min_id as i32;
+    fn accepts_at(&self, s: i32) -> bool {
+        s != -1 && self.states[s as usize].accept
+    }
+
+    // the sink only moves to itself
+    fn next_or_sink(&self, s: i32, c: u8) -> i32 {
+        if s == -1 {
+            -1
+        } else {
+            self.get_trans(s as usize, c)
+        }
+    }
+
+    // states reachable from the start state that can still reach an accepting state
+    fn useful_states(&self) -> Vec<bool> {
+        let n = self.size();
+        let mut reachable = vec![false; n];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        if n > 0 {
+            reachable[self.start] = true;
+            queue.push_back(self.start);
+        }
+        let mut reverse: Vec<Vec<usize>> = vec![Vec::new(); n];
+        while let Some(s) = queue.pop_front() {
+            for t in &self.states[s].t {
+                if *t == -1 {
+                    continue;
+                }
+                let t = *t as usize;
+                reverse[t].push(s);
+                if !reachable[t] {
+                    reachable[t] = true;
+                    queue.push_back(t);
+                }
+            }
+        }
+        let mut useful = vec![false; n];
+        for s in 0..n {
+            if reachable[s] && self.states[s].accept {
+                useful[s] = true;
+                queue.push_back(s);
             }
         }
+        while let Some(s) = queue.pop_front() {
+            for p in &reverse[s] {
+                if !useful[*p] {
+                    useful[*p] = true;
+                    queue.push_back(*p);
+                }
+            }
+        }
+        useful
+    }
+
+    // Moore's partition refinement. Useless states are dropped and stand for the
+    // implicit sink (-1), so the result is the minimal partial dfa.
+    pub fn minimize(&self) -> Self {
+        let useful = self.useful_states();
+        let mut class: Vec<i32> = (0..self.size())
+            .map(|s| {
+                if !useful[s] {
+                    -1
+                } else if self.states[s].accept {
+                    1
+                } else {
+                    0
+                }
+            })
+            .collect();
+        let mut class_num = 0;
+        loop {
+            let mut signature2class: HashMap<(i32, Vec<i32>), i32> = HashMap::new();
+            let mut next_class = vec![-1; self.size()];
+            for s in 0..self.size() {
+                if class[s] == -1 {
+                    continue;
+                }
+                let signature = (
+                    class[s],
+                    self.states[s]
+                        .t
+                        .iter()
+                        .map(|t| if *t == -1 { -1 } else { class[*t as usize] })
+                        .collect(),
+                );
+                let id = signature2class.len() as i32;
+                next_class[s] = *signature2class.entry(signature).or_insert(id);
+            }
+            class = next_class;
+            if signature2class.len() == class_num {
+                break;
+            }
+            class_num = signature2class.len();
+        }
 
+        // renumber the classes in breadth-first order from the start state
         let mut min_dfa = DFA {
             states: Vec::new(),
-            start: min_dfa_ids[self.start] as usize,
+            start: 0,
+            alphabet: self.alphabet.clone(),
             is_minimum: true,
         };
+        let mut class2id: HashMap<i32, i32> = HashMap::new();
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        if self.size() > 0 && class[self.start] != -1 {
+            class2id.insert(class[self.start], 0);
+            queue.push_back(self.start);
+        }
+        while let Some(s) = queue.pop_front() {
+            let mut state = State::new(class2id[&class[s]], self.states[s].accept);
+            for (ch, t) in self.states[s].t.iter().enumerate() {
+                if *t == -1 || class[*t as usize] == -1 {
+                    continue;
+                }
+                let t = *t as usize;
+                let id = class2id.len() as i32;
+                let id = *class2id.entry(class[t]).or_insert_with(|| {
+                    queue.push_back(t);
+                    id
+                });
+                state.add_trans(id, ch);
+            }
+            min_dfa.add_state(state);
+        }
+        if min_dfa.states.is_empty() {
+            // the empty language: a single dead state, which loops on every
+            // letter so that it is not a second sink beside the implicit one
+            let mut dead = State::new(0, false);
+            for ch in self.alphabet.iter() {
+                dead.add_trans(0, ch);
+            }
+            min_dfa.add_state(dead);
+        }
+        debug_assert!(min_dfa.is_minimal());
+        min_dfa
+    }
 
-        for (min_id, m) in transition_map.iter().enumerate() {
-            let trans = (m.0)
-                .0
-                .iter()
-                .map(|t| {
-                    if *t != -1 {
-                        min_dfa_ids[*t as usize]
-                    } else {
-                        -1
+    // table-filling check that every state is useful and no two states are equivalent
+    pub fn is_minimal(&self) -> bool {
+        let n = self.size();
+        let useful = self.useful_states();
+        if n == 1 && !self.states[0].accept {
+            return self.alphabet.iter().all(|c| self.states[0].t[c] == 0);
+        }
+        if useful.iter().any(|u| !u) {
+            return false;
+        }
+        // distinct[p][q] is true once p and q are known to be distinguishable;
+        // index n stands for the implicit sink
+        let mut distinct = vec![vec![false; n + 1]; n + 1];
+        let accept = |s: usize| s < n && self.states[s].accept;
+        let next = |s: usize, c: usize| {
+            if s == n || self.states[s].t[c] == -1 {
+                n
+            } else {
+                self.states[s].t[c] as usize
+            }
+        };
+        for (p, row) in distinct.iter_mut().enumerate() {
+            for (q, d) in row.iter_mut().enumerate() {
+                *d = accept(p) != accept(q);
+            }
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for p in 0..=n {
+                for q in 0..p {
+                    if distinct[p][q] {
+                        continue;
                     }
-                })
-                .collect();
-
-            min_dfa.add_state(State {
-                t: trans,
-                id: min_id as i32,
-                accept: (m.0).1,
-            });
+                    if self
+                        .alphabet
+                        .iter()
+                        .any(|c| distinct[next(p, c)][next(q, c)])
+                    {
+                        distinct[p][q] = true;
+                        distinct[q][p] = true;
+                        changed = true;
+                    }
+                }
+            }
         }
-        min_dfa
+        (0..=n).all(|p| (0..p).all(|q| distinct[p][q]))
     }
 
     pub fn to_graphviz(&self) {
@@ -224,6 +456,61 @@ impl DFA {
         println!("}}");
     }
 
+    // Ok if both automata accept the same language, otherwise the shortest (and
+    // among those the smallest) word accepted by exactly one of them
+    pub fn equivalent(&self, other: &DFA) -> Result<(), Vec<u8>> {
+        match self.shortest_word(other, |p, q| p != q) {
+            None => Ok(()),
+            Some(word) => Err(word),
+        }
+    }
+
+    // Ok if every word accepted here is accepted by `other`, otherwise the
+    // shortest word accepted here but not by `other`
+    pub fn is_subset_of(&self, other: &DFA) -> Result<(), Vec<u8>> {
+        match self.shortest_word(other, |p, q| p && !q) {
+            None => Ok(()),
+            Some(word) => Err(word),
+        }
+    }
+
+    // breadth-first search of the product for the shortest word w with
+    // `op(self accepts w, other accepts w)`; `op(false, false)` must be false
+    fn shortest_word(&self, other: &DFA, op: fn(bool, bool) -> bool) -> Option<Vec<u8>> {
+        let start = (self.start_or_sink(), other.start_or_sink());
+        let mut parent: HashMap<(i32, i32), ((i32, i32), u8)> = HashMap::new();
+        let mut visited: HashSet<(i32, i32)> = HashSet::new();
+        let mut queue: VecDeque<(i32, i32)> = VecDeque::new();
+        let mut alphabet = self.alphabet.clone();
+        alphabet.union_with(&other.alphabet);
+        visited.insert(start);
+        queue.push_back(start);
+        while let Some((p, q)) = queue.pop_front() {
+            if op(self.accepts_at(p), other.accepts_at(q)) {
+                let mut word = Vec::new();
+                let mut pair = (p, q);
+                while let Some(&(prev, c)) = parent.get(&pair) {
+                    word.push(c);
+                    pair = prev;
+                }
+                word.reverse();
+                return Some(word);
+            }
+            for c in alphabet.iter() {
+                let pair = (
+                    self.next_or_sink(p, c as u8),
+                    other.next_or_sink(q, c as u8),
+                );
+                if pair == (-1, -1) || !visited.insert(pair) {
+                    continue;
+                }
+                parent.insert(pair, ((p, q), c as u8));
+                queue.push_back(pair);
+            }
+        }
+        None
+    }
+
     pub fn accept(&self, input: &Vec<u8>) -> bool {
         let mut state = self.start as i32;
         for c in input {
@@ -235,3 +522,161 @@ impl DFA {
         self.states[state as usize].accept
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Parser;
+    use syntactic_monoid::SyntacticMonoid;
+
+    fn alphabet(letters: &str) -> BitSet {
+        letters.bytes().map(|a| a as usize).collect()
+    }
+
+    fn dfa(pattern: &str, letters: &str) -> DFA {
+        let re = Parser::new().parse(pattern).unwrap();
+        DFA::construct_from_nfa(&NFA::construct(&re, &alphabet(letters)))
+    }
+
+    // an automaton over "ab" from rows of (target by a, target by b, accept)
+    fn table(rows: &[(i32, i32, bool)]) -> DFA {
+        let mut dfa = DFA::new();
+        dfa.alphabet = alphabet("ab");
+        for (id, &(a, b, accept)) in rows.iter().enumerate() {
+            let mut s = State::new(id as i32, accept);
+            s.add_trans(a, b'a' as usize);
+            s.add_trans(b, b'b' as usize);
+            dfa.add_state(s);
+        }
+        dfa
+    }
+
+    #[test]
+    fn minimal_sizes() {
+        for &(pattern, size) in &[
+            ("(a|b)*abb", 4),
+            ("(ab)*", 2),
+            ("a*b*", 2),
+            ("(a|b)*", 1),
+            ("(a|b)*a(a|b)(a|b)", 8),
+            ("@", 1),
+        ] {
+            let min = dfa(pattern, "ab").minimize();
+            assert_eq!(min.size(), size, "{}", pattern);
+            assert!(min.is_minimal());
+            assert!(min.is_minimum());
+        }
+    }
+
+    #[test]
+    fn empty_language() {
+        let min = dfa("@", "ab").minimize();
+        assert!(!min.states[0].accept);
+        assert!(!min.accept(&Vec::new()));
+        assert_eq!(min.get_trans(0, b'a'), 0);
+        assert!(table(&[(0, 0, false)]).is_minimal());
+        assert!(!table(&[(-1, -1, false)]).is_minimal());
+        assert!(!table(&[(0, -1, false)]).is_minimal());
+        // the dead state and the implicit sink are one element
+        let mut sm = SyntacticMonoid::new();
+        sm.construct(&min);
+        assert_eq!(sm.size(), 1);
+    }
+
+    #[test]
+    fn sink_state() {
+        // a*b with an explicit sink 2
+        let dfa = table(&[(0, 1, false), (2, 2, true), (2, 2, false)]);
+        assert!(!dfa.is_minimal());
+        let min = dfa.minimize();
+        assert_eq!(min.size(), 2);
+        assert!(min.is_minimal());
+        assert_eq!(min.equivalent(&dfa), Ok(()));
+        assert_eq!(min.get_trans(1, b'a'), -1);
+    }
+
+    #[test]
+    fn unreachable_states() {
+        let dfa = table(&[(0, 1, false), (-1, -1, true), (1, 2, true)]);
+        assert!(!dfa.is_minimal());
+        let min = dfa.minimize();
+        assert_eq!(min.size(), 2);
+        assert_eq!(min.equivalent(&dfa), Ok(()));
+    }
+
+    #[test]
+    fn equivalent_states() {
+        // 1 and 2 both accept and loop back to each other
+        let dfa = table(&[(1, 2, false), (2, 1, true), (1, 2, true)]);
+        assert!(!dfa.is_minimal());
+        assert_eq!(dfa.minimize().size(), 2);
+    }
+
+    #[test]
+    fn equivalence() {
+        assert_eq!(
+            dfa("(a|b)*", "ab").equivalent(&dfa("(a*b*)*", "ab")),
+            Ok(())
+        );
+        assert_eq!(dfa("(ab)*a", "ab").equivalent(&dfa("a(ba)*", "ab")), Ok(()));
+        assert_eq!(
+            dfa("ab|ba", "ab").equivalent(&dfa("ba", "ab")),
+            Err(b"ab".to_vec())
+        );
+        assert_eq!(
+            dfa("a*", "ab").equivalent(&dfa("a+", "ab")),
+            Err(Vec::new())
+        );
+    }
+
+    #[test]
+    fn inclusion() {
+        assert_eq!(dfa("a*", "ab").is_subset_of(&dfa("(a|b)*", "ab")), Ok(()));
+        assert_eq!(
+            dfa("(a|b)*", "ab").is_subset_of(&dfa("a*", "ab")),
+            Err(b"b".to_vec())
+        );
+        assert_eq!(dfa("@", "ab").is_subset_of(&dfa("a", "ab")), Ok(()));
+        assert_eq!(dfa("ab|b", "ab").is_subset_of(&dfa("a*b", "ab")), Ok(()));
+    }
+
+    #[test]
+    fn union() {
+        // over different alphabets a letter missing on one side leads to its sink
+        let union = dfa("a*", "a").union(&dfa("b*", "b"));
+        assert_eq!(union.alphabet, alphabet("ab"));
+        assert_eq!(union.equivalent(&dfa("a*|b*", "ab")), Ok(()));
+        // the pair of sinks stays implicit: start, (a, sink) and (sink, b)
+        let union = dfa("a", "a").minimize().union(&dfa("b", "b").minimize());
+        assert_eq!(union.size(), 3);
+        assert_eq!(union.get_trans(1, b'a'), -1);
+        assert_eq!(union.get_trans(1, b'b'), -1);
+        assert_eq!(union.equivalent(&dfa("a|b", "ab")), Ok(()));
+    }
+
+    #[test]
+    fn difference() {
+        let difference = dfa("(a|b)*", "ab").difference(&dfa("a*", "a"));
+        assert_eq!(difference.equivalent(&dfa("(a|b)*b(a|b)*", "ab")), Ok(()));
+        let difference = dfa("a*", "a").difference(&dfa("(a|b)*", "ab"));
+        assert_eq!(difference.equivalent(&dfa("@", "ab")), Ok(()));
+        assert!(!difference.accept(&b"aa".to_vec()));
+    }
+
+    #[test]
+    fn symmetric_difference() {
+        let symmetric = dfa("a(a|b)*", "ab").symmetric_difference(&dfa("(a|b)*b", "ab"));
+        assert_eq!(
+            symmetric.equivalent(&dfa("a|a(a|b)*a|b|b(a|b)*b", "ab")),
+            Ok(())
+        );
+        let symmetric = dfa("a*", "a").symmetric_difference(&dfa("b*", "b"));
+        assert_eq!(symmetric.equivalent(&dfa("a+|b+", "ab")), Ok(()));
+        assert_eq!(
+            dfa("(ab)*", "ab")
+                .symmetric_difference(&dfa("(ab)*", "ab"))
+                .equivalent(&dfa("@", "ab")),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use syntactic_monoid::{ElemSet, ElemType, SyntacticMonoid};
+
+// a D-class drawn as an egg-box: rows are its R-classes, columns its L-classes
+#[derive(Debug, Clone)]
+pub struct DClass {
+    pub elements: Vec<ElemType>,
+    pub r_classes: Vec<usize>,
+    pub l_classes: Vec<usize>,
+    pub regular: bool,
+}
+
+// Green's relations of a finite monoid, where J = D:
+//   s R t iff sM = tM,  s L t iff Ms = Mt,  s J t iff MsM = MtM,  H = R ∩ L.
+// Classes are numbered by their smallest element.
+pub struct GreenRelations<'a> {
+    monoid: &'a SyntacticMonoid,
+    pub r_classes: Vec<Vec<ElemType>>,
+    pub l_classes: Vec<Vec<ElemType>>,
+    pub h_classes: Vec<Vec<ElemType>>,
+    pub d_classes: Vec<DClass>,
+    r_class: Vec<usize>,
+    l_class: Vec<usize>,
+    h_class: Vec<usize>,
+    d_class: Vec<usize>,
+    rl2h: HashMap<(usize, usize), usize>,
+    ideals: Vec<ElemSet>, // the two-sided ideal MsM of each D-class
+}
+
+// groups the elements by `key`, returning the classes and the class of each element
+fn partition<K: Hash + Eq, F: Fn(ElemType) -> K>(
+    size: usize,
+    key: F,
+) -> (Vec<Vec<ElemType>>, Vec<usize>) {
+    let mut key2class: HashMap<K, usize> = HashMap::new();
+    let mut classes: Vec<Vec<ElemType>> = Vec::new();
+    let mut class_of = Vec::with_capacity(size);
+    for e in 0..size {
+        let id = classes.len();
+        let id = *key2class.entry(key(e)).or_insert(id);
+        if id == classes.len() {
+            classes.push(Vec::new());
+        }
+        classes[id].push(e);
+        class_of.push(id);
+    }
+    (classes, class_of)
+}
+
+pub(crate) fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl<'a> GreenRelations<'a> {
+    pub fn new(monoid: &'a SyntacticMonoid) -> Self {
+        let n = monoid.size();
+        let m = monoid.make_elemset();
+        let (r_classes, r_class) = partition(n, |s| monoid.left_multiply(s, &m));
+        let (l_classes, l_class) = partition(n, |s| monoid.right_multiply(&m, s));
+        let (h_classes, h_class) = partition(n, |s| (r_class[s], l_class[s]));
+        let (d_elements, d_class) = partition(n, |s| {
+            monoid.elemset_multiply(&m, &monoid.left_multiply(s, &m))
+        });
+        let ideals = d_elements
+            .iter()
+            .map(|d| monoid.elemset_multiply(&m, &monoid.left_multiply(d[0], &m)))
+            .collect();
+
+        let mut rl2h = HashMap::new();
+        for (h, class) in h_classes.iter().enumerate() {
+            rl2h.insert((r_class[class[0]], l_class[class[0]]), h);
+        }
+        let d_classes = d_elements
+            .into_iter()
+            .map(|elements| {
+                let mut r_classes: Vec<usize> = elements.iter().map(|e| r_class[*e]).collect();
+                let mut l_classes: Vec<usize> = elements.iter().map(|e| l_class[*e]).collect();
+                r_classes.sort();
+                r_classes.dedup();
+                l_classes.sort();
+                l_classes.dedup();
+                let regular = elements.iter().any(|e| monoid.is_idempotent(*e));
+                DClass {
+                    elements,
+                    r_classes,
+                    l_classes,
+                    regular,
+                }
+            })
+            .collect();
+
+        GreenRelations {
+            monoid,
+            r_classes,
+            l_classes,
+            h_classes,
+            d_classes,
+            r_class,
+            l_class,
+            h_class,
+            d_class,
+            rl2h,
+            ideals,
+        }
+    }
+
+    pub fn r_class_of(&self, e: ElemType) -> usize {
+        self.r_class[e]
+    }
+
+    pub fn l_class_of(&self, e: ElemType) -> usize {
+        self.l_class[e]
+    }
+
+    pub fn h_class_of(&self, e: ElemType) -> usize {
+        self.h_class[e]
+    }
+
+    pub fn d_class_of(&self, e: ElemType) -> usize {
+        self.d_class[e]
+    }
+
+    // the H-class at the intersection of an R-class and an L-class of the same D-class
+    pub fn h_class_at(&self, r: usize, l: usize) -> Option<usize> {
+        self.rl2h.get(&(r, l)).cloned()
+    }
+
+    // an H-class is a maximal subgroup iff it contains an idempotent, its identity
+    pub fn group_identity(&self, h: usize) -> Option<ElemType> {
+        self.h_classes[h]
+            .iter()
+            .cloned()
+            .find(|e| self.monoid.is_idempotent(*e))
+    }
+
+    pub fn maximal_subgroups(&self) -> Vec<usize> {
+        (0..self.h_classes.len())
+            .filter(|h| self.group_identity(*h).is_some())
+            .collect()
+    }
+
+    // d1 <=_J d2, i.e. the ideal of d1 is contained in the ideal of d2
+    pub fn j_below(&self, d1: usize, d2: usize) -> bool {
+        self.ideals[d2].contains(&self.d_classes[d1].elements[0])
+    }
+
+    // the covering pairs (lower, upper) of the J-order on D-classes
+    pub fn j_order_hasse(&self) -> Vec<(usize, usize)> {
+        let n = self.d_classes.len();
+        let below = |d1: usize, d2: usize| d1 != d2 && self.j_below(d1, d2);
+        let mut covers = Vec::new();
+        for lower in 0..n {
+            for upper in 0..n {
+                if below(lower, upper) && !(0..n).any(|d| below(lower, d) && below(d, upper)) {
+                    covers.push((lower, upper));
+                }
+            }
+        }
+        covers
+    }
+
+    // the minimal ideal of a finite monoid is a single D-class, below all others
+    pub fn minimal_ideal(&self) -> usize {
+        (0..self.d_classes.len())
+            .find(|d| (0..self.d_classes.len()).all(|e| self.j_below(*d, e)))
+            .unwrap()
+    }
+
+    // the zero, if the minimal ideal is a single element
+    pub fn zero(&self) -> Option<ElemType> {
+        let d = &self.d_classes[self.minimal_ideal()];
+        if d.elements.len() == 1 {
+            Some(d.elements[0])
+        } else {
+            None
+        }
+    }
+
+    // the Hasse diagram of the J-order with the identity on top; non-regular
+    // D-classes are dashed and the minimal ideal is drawn with a double border
+    pub fn j_order_to_graphviz(&self) {
+        let minimal = self.minimal_ideal();
+        println!("digraph JOrder {{");
+        for (i, d) in self.d_classes.iter().enumerate() {
+            let words: Vec<String> = d.elements.iter().map(|e| self.monoid.label(*e)).collect();
+            print!(
+                "  {} [ shape=box, label = \"{}\\n{} element{}, {}\"",
+                i,
+                escape_dot(&words.join(" ")),
+                d.elements.len(),
+                if d.elements.len() == 1 { "" } else { "s" },
+                if d.regular { "regular" } else { "non-regular" }
+            );
+            if !d.regular {
+                print!(", style=dashed");
+            }
+            if i == minimal {
+                print!(", peripheries=2");
+            }
+            println!(" ];");
+        }
+        for (lower, upper) in self.j_order_hasse() {
+            println!(" {} -> {};", upper, lower);
+        }
+        println!("}}");
+    }
+
+    // idempotents are marked with a trailing *
+    fn cell_label(&self, h: usize) -> String {
+        self.h_classes[h]
+            .iter()
+            .map(|e| {
+                if self.monoid.is_idempotent(*e) {
+                    format!("{}*", self.monoid.label(*e))
+                } else {
+                    self.monoid.label(*e)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn print_eggbox(&self) {
+        for (i, d) in self.d_classes.iter().enumerate() {
+            println!(
+                "D-class {} ({}, {} element{})",
+                i,
+                if d.regular { "regular" } else { "non-regular" },
+                d.elements.len(),
+                if d.elements.len() == 1 { "" } else { "s" }
+            );
+            let cells: Vec<Vec<String>> = d
+                .r_classes
+                .iter()
+                .map(|r| {
+                    d.l_classes
+                        .iter()
+                        .map(|l| self.cell_label(self.h_class_at(*r, *l).unwrap()))
+                        .collect()
+                })
+                .collect();
+            let widths: Vec<usize> = (0..d.l_classes.len())
+                .map(|j| {
+                    cells
+                        .iter()
+                        .map(|row| row[j].chars().count())
+                        .max()
+                        .unwrap()
+                })
+                .collect();
+            let border: String = widths
+                .iter()
+                .map(|w| format!("+{}", "-".repeat(w + 2)))
+                .collect::<String>()
+                + "+";
+            println!("{}", border);
+            for row in &cells {
+                for (cell, w) in row.iter().zip(&widths) {
+                    print!("| {}{} ", cell, " ".repeat(w - cell.chars().count()));
+                }
+                println!("|");
+                println!("{}", border);
+            }
+        }
+    }
+
+    // one table per D-class; maximal subgroups are shaded
+    pub fn eggbox_to_graphviz(&self) {
+        println!("digraph EggBox {{");
+        println!("  node [ shape=plaintext ];");
+        for (i, d) in self.d_classes.iter().enumerate() {
+            print!(
+                "  d{} [ label=<<table border=\"0\" cellborder=\"1\" cellspacing=\"0\">",
+                i
+            );
+            for r in &d.r_classes {
+                print!("<tr>");
+                for l in &d.l_classes {
+                    let h = self.h_class_at(*r, *l).unwrap();
+                    if self.group_identity(h).is_some() {
+                        print!(
+                            "<td bgcolor=\"lightgray\">{}</td>",
+                            escape_html(&self.cell_label(h))
+                        );
+                    } else {
+                        print!("<td>{}</td>", escape_html(&self.cell_label(h)));
+                    }
+                }
+                print!("</tr>");
+            }
+            println!("</table>> ];");
+        }
+        println!("}}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntactic_monoid::tests::monoid;
+
+    fn labels(sm: &SyntacticMonoid, elements: &[ElemType]) -> Vec<String> {
+        elements.iter().map(|e| sm.label(*e)).collect()
+    }
+
+    #[test]
+    fn relations_match_the_definitions() {
+        for &(pattern, letters) in &[("(ab)*", "ab"), ("(a|ba)*", "ab"), ("(aa|b)*ab", "ab")] {
+            let sm = monoid(pattern, letters);
+            let green = GreenRelations::new(&sm);
+            let n = sm.size();
+            // s R t iff s x = t and t y = s for some x, y
+            let r = |s: ElemType, t: ElemType| {
+                (0..n).any(|x| sm.multiply(s, x) == t) && (0..n).any(|y| sm.multiply(t, y) == s)
+            };
+            let l = |s: ElemType, t: ElemType| {
+                (0..n).any(|x| sm.multiply(x, s) == t) && (0..n).any(|y| sm.multiply(y, t) == s)
+            };
+            for s in 0..n {
+                for t in 0..n {
+                    assert_eq!(green.r_class_of(s) == green.r_class_of(t), r(s, t));
+                    assert_eq!(green.l_class_of(s) == green.l_class_of(t), l(s, t));
+                    assert_eq!(
+                        green.h_class_of(s) == green.h_class_of(t),
+                        r(s, t) && l(s, t)
+                    );
+                    let d = (0..n).any(|u| r(s, u) && l(u, t));
+                    assert_eq!(green.d_class_of(s) == green.d_class_of(t), d);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn brandt_monoid() {
+        // (ab)* has the 2x2 Brandt monoid B2 with an identity as syntactic monoid
+        let sm = monoid("(ab)*", "ab");
+        let green = GreenRelations::new(&sm);
+        let classes: Vec<Vec<String>> = green
+            .d_classes
+            .iter()
+            .map(|d| labels(&sm, &d.elements))
+            .collect();
+        assert_eq!(
+            classes,
+            vec![
+                vec!["[ε]"],
+                vec!["[a]", "[b]", "[ab]", "[ba]"],
+                vec!["[aa]"]
+            ]
+        );
+        let middle = &green.d_classes[1];
+        assert_eq!((middle.r_classes.len(), middle.l_classes.len()), (2, 2));
+        assert!(green.d_classes.iter().all(|d| d.regular));
+        assert_eq!(green.zero(), sm.morphism(b"aa").ok());
+        assert_eq!(green.j_order_hasse(), vec![(1, 0), (2, 1)]);
+        assert_eq!(green.maximal_subgroups().len(), 4);
+        let (a, b) = (sm.letter(b'a').unwrap(), sm.letter(b'b').unwrap());
+        let h = green
+            .h_class_at(green.r_class_of(a), green.l_class_of(b))
+            .unwrap();
+        assert_eq!(labels(&sm, &green.h_classes[h]), vec!["[ab]"]);
+        assert_eq!(green.group_identity(green.h_class_of(a)), None);
+    }
+
+    #[test]
+    fn group() {
+        // the syntactic monoid of (aa)* is the group Z_2, a single H-class
+        let sm = monoid("(aa)*", "a");
+        let green = GreenRelations::new(&sm);
+        assert_eq!(green.h_classes.len(), 1);
+        assert_eq!(green.group_identity(0), Some(0));
+        assert_eq!(green.zero(), None);
+        assert_eq!(green.minimal_ideal(), 0);
+    }
+
+    #[test]
+    fn non_regular_class() {
+        // in the monoid of the finite language {ab}, a x a = 0 for every x
+        let sm = monoid("ab", "ab");
+        let green = GreenRelations::new(&sm);
+        let a = green.d_class_of(sm.letter(b'a').unwrap());
+        assert!(!green.d_classes[a].regular);
+        assert!(green.j_below(a, green.d_class_of(0)));
+        assert!(!green.j_below(green.d_class_of(0), a));
+    }
+}
@@ -0,0 +1,461 @@
+use green::GreenRelations;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use syntactic_monoid::{ElemType, SyntacticMonoid};
+
+// A maximal subgroup of the monoid, the H-class of an idempotent. Its elements
+// permute the dfa states fixed by the idempotent, which gives it as a
+// permutation group (the Schützenberger group of the H-class).
+pub struct MaximalSubgroup<'a> {
+    monoid: &'a SyntacticMonoid,
+    pub identity: ElemType,
+    pub elements: Vec<ElemType>,
+}
+
+type Subgroup = BTreeSet<ElemType>;
+
+fn factorial(n: usize) -> usize {
+    (1..=n).product()
+}
+
+// the prime factorization as (p, a) with p^a exactly dividing n
+#[allow(clippy::manual_is_multiple_of)] // `is_multiple_of` needs Rust 1.87
+fn factorize(mut n: usize) -> Vec<(usize, u32)> {
+    let mut factors = Vec::new();
+    let mut p = 2;
+    while n > 1 {
+        let mut a = 0;
+        while n % p == 0 {
+            n /= p;
+            a += 1;
+        }
+        if a > 0 {
+            factors.push((p, a));
+        }
+        p += 1;
+    }
+    factors
+}
+
+impl<'a> MaximalSubgroup<'a> {
+    pub fn new(monoid: &'a SyntacticMonoid, green: &GreenRelations, identity: ElemType) -> Self {
+        MaximalSubgroup {
+            monoid,
+            identity,
+            elements: green.h_classes[green.h_class_of(identity)].clone(),
+        }
+    }
+
+    // one maximal subgroup per regular D-class, where they are all isomorphic
+    pub fn representatives(monoid: &'a SyntacticMonoid, green: &GreenRelations) -> Vec<Self> {
+        green
+            .d_classes
+            .iter()
+            .filter(|d| d.regular)
+            .map(|d| {
+                let e = *d
+                    .elements
+                    .iter()
+                    .find(|e| monoid.is_idempotent(**e))
+                    .unwrap();
+                MaximalSubgroup::new(monoid, green, e)
+            })
+            .collect()
+    }
+
+    pub fn order(&self) -> usize {
+        self.elements.len()
+    }
+
+    fn power(&self, x: ElemType, k: usize) -> ElemType {
+        (0..k).fold(self.identity, |p, _| self.monoid.multiply(p, x))
+    }
+
+    // the least k >= 1 with x^k the identity of the group
+    pub fn element_order(&self, x: ElemType) -> usize {
+        let mut p = x;
+        let mut k = 1;
+        while p != self.identity {
+            p = self.monoid.multiply(p, x);
+            k += 1;
+        }
+        k
+    }
+
+    fn inverse(&self, x: ElemType) -> ElemType {
+        self.power(x, self.element_order(x) - 1)
+    }
+
+    // the subgroup generated by some elements
+    fn generate(&self, generators: &[ElemType]) -> Subgroup {
+        let mut subgroup = Subgroup::new();
+        subgroup.insert(self.identity);
+        let mut queue = vec![self.identity];
+        while let Some(x) = queue.pop() {
+            for g in generators {
+                let y = self.monoid.multiply(x, *g);
+                if subgroup.insert(y) {
+                    queue.push(y);
+                }
+            }
+        }
+        subgroup
+    }
+
+    // a small generating set, chosen greedily in element order
+    pub fn generators(&self) -> Vec<ElemType> {
+        let mut generators = Vec::new();
+        let mut subgroup = self.generate(&generators);
+        for x in &self.elements {
+            if !subgroup.contains(x) {
+                generators.push(*x);
+                subgroup = self.generate(&generators);
+            }
+        }
+        generators
+    }
+
+    pub fn is_abelian(&self) -> bool {
+        self.elements.iter().all(|x| {
+            self.elements
+                .iter()
+                .all(|y| self.monoid.multiply(*x, *y) == self.monoid.multiply(*y, *x))
+        })
+    }
+
+    pub fn is_cyclic(&self) -> bool {
+        self.elements
+            .iter()
+            .any(|x| self.element_order(*x) == self.order())
+    }
+
+    // the subgroup generated by the commutators of a subgroup
+    fn derived_subgroup(&self, subgroup: &Subgroup) -> Subgroup {
+        let mut commutators = BTreeSet::new();
+        for x in subgroup {
+            for y in subgroup {
+                let xy = self.monoid.multiply(*x, *y);
+                let yx = self.monoid.multiply(*y, *x);
+                commutators.insert(self.monoid.multiply(xy, self.inverse(yx)));
+            }
+        }
+        self.generate(&commutators.into_iter().collect::<Vec<_>>())
+    }
+
+    // whether the derived series reaches the trivial group
+    pub fn is_solvable(&self) -> bool {
+        let mut subgroup: Subgroup = self.elements.iter().cloned().collect();
+        loop {
+            if subgroup.len() == 1 {
+                return true;
+            }
+            let derived = self.derived_subgroup(&subgroup);
+            if derived.len() == subgroup.len() {
+                return false;
+            }
+            subgroup = derived;
+        }
+    }
+
+    // the dfa states fixed by the identity, on which the group acts
+    pub fn points(&self) -> Vec<usize> {
+        self.monoid
+            .images(self.identity)
+            .iter()
+            .enumerate()
+            .filter(|&(q, image)| *image == q as i32)
+            .map(|(q, _)| q)
+            .collect()
+    }
+
+    // the disjoint cycles of states moved by x, each from its smallest state
+    fn disjoint_cycles(&self, x: ElemType) -> Vec<Vec<usize>> {
+        let images = self.monoid.images(x);
+        let mut seen = BTreeSet::new();
+        let mut cycles = Vec::new();
+        for q in self.points() {
+            if seen.contains(&q) || images[q] == q as i32 {
+                continue;
+            }
+            let mut cycle = vec![q];
+            seen.insert(q);
+            let mut p = images[q] as usize;
+            while p != q {
+                cycle.push(p);
+                seen.insert(p);
+                p = images[p] as usize;
+            }
+            cycles.push(cycle);
+        }
+        cycles
+    }
+
+    // x as a product of disjoint cycles of states, e.g. (0 1 2)(3 4)
+    pub fn cycles(&self, x: ElemType) -> String {
+        let cycles = self.disjoint_cycles(x);
+        if cycles.is_empty() {
+            return "()".to_string();
+        }
+        cycles
+            .iter()
+            .map(|cycle| {
+                let states: Vec<String> = cycle.iter().map(|q| q.to_string()).collect();
+                format!("({})", states.join(" "))
+            })
+            .collect()
+    }
+
+    // a cycle of length l is a product of l - 1 transpositions
+    #[allow(clippy::manual_is_multiple_of)] // `is_multiple_of` needs Rust 1.87
+    fn is_even(&self, x: ElemType) -> bool {
+        let transpositions: usize = self.disjoint_cycles(x).iter().map(|c| c.len() - 1).sum();
+        transpositions % 2 == 0
+    }
+
+    // the abelian group as a product of cyclic groups of prime power order:
+    // the number of x with x^(p^k) = 1 is p^(sum of min(k, a_i)) for Z_(p^a_i)
+    fn abelian_name(&self) -> String {
+        let mut factors = Vec::new();
+        for (p, a) in factorize(self.order()) {
+            let exponent = |k: u32| {
+                let count = self
+                    .elements
+                    .iter()
+                    .filter(|x| self.power(**x, p.pow(k)) == self.identity)
+                    .count();
+                (0..).find(|s| p.pow(*s) >= count).unwrap()
+            };
+            // at_least[k]: the number of cyclic factors of order at least p^k
+            let at_least: Vec<u32> = (0..=a + 1)
+                .map(|k| {
+                    if k == 0 {
+                        0
+                    } else {
+                        exponent(k) - exponent(k - 1)
+                    }
+                })
+                .collect();
+            for k in 1..=a {
+                for _ in 0..at_least[k as usize] - at_least[k as usize + 1] {
+                    factors.push(format!("Z_{}", p.pow(k)));
+                }
+            }
+        }
+        factors.join(" × ")
+    }
+
+    // a name for small or well-known groups
+    #[allow(clippy::manual_is_multiple_of)] // `is_multiple_of` needs Rust 1.87
+    pub fn name(&self) -> Option<String> {
+        let n = self.order();
+        if n == 1 {
+            return Some("trivial".to_string());
+        }
+        if self.is_cyclic() {
+            return Some(format!("Z_{}", n));
+        }
+        if self.is_abelian() {
+            return Some(self.abelian_name());
+        }
+        let degree = self.points().len();
+        if n == factorial(degree) {
+            return Some(format!("S_{}", degree));
+        }
+        if 2 * n == factorial(degree) && self.elements.iter().all(|x| self.is_even(*x)) {
+            return Some(format!("A_{}", degree));
+        }
+        let mut orders: BTreeMap<usize, usize> = BTreeMap::new();
+        for x in &self.elements {
+            *orders.entry(self.element_order(*x)).or_insert(0) += 1;
+        }
+        let involutions = orders.get(&2).cloned().unwrap_or(0);
+        let solvable = self.is_solvable();
+        match n {
+            6 => Some("S_3".to_string()),
+            8 if involutions == 1 => Some("Q_8".to_string()),
+            8 => Some("D_4".to_string()),
+            12 if involutions == 3 && !orders.contains_key(&6) => Some("A_4".to_string()),
+            24 if orders == [(1, 1), (2, 9), (3, 8), (4, 6)].iter().cloned().collect() => {
+                Some("S_4".to_string())
+            }
+            60 if !solvable => Some("A_5".to_string()),
+            120 if !solvable && involutions == 25 => Some("S_5".to_string()),
+            // a rotation of order n/2 and reflections outside it
+            _ if n % 2 == 0 && orders.contains_key(&(n / 2)) && involutions >= n / 2 => {
+                Some(format!("D_{}", n / 2))
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn print_maximal_subgroups(monoid: &SyntacticMonoid) {
+    let green = GreenRelations::new(monoid);
+    let groups = MaximalSubgroup::representatives(monoid, &green);
+    let mut trivial = true;
+    for g in groups.iter().filter(|g| g.order() > 1) {
+        trivial = false;
+        let mut properties = vec![format!("order {}", g.order())];
+        if g.is_abelian() {
+            properties.push("abelian".to_string());
+        }
+        if g.is_cyclic() {
+            properties.push("cyclic".to_string());
+        }
+        properties.push(if g.is_solvable() {
+            "solvable".to_string()
+        } else {
+            "not solvable".to_string()
+        });
+        let generators: Vec<String> = g.generators().iter().map(|x| g.cycles(*x)).collect();
+        println!(
+            "  H{}: {} ({}), generated by {} on states {}",
+            monoid.label(g.identity),
+            g.name()
+                .unwrap_or_else(|| format!("group of order {}", g.order())),
+            properties.join(", "),
+            generators.join(", "),
+            g.points()
+                .iter()
+                .map(|q| q.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+    }
+    if trivial {
+        println!("  all maximal subgroups are trivial");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dfa::{State, DFA};
+    use syntactic_monoid::tests::monoid;
+
+    // the monoid of a permutation automaton whose letters a, b, ... act on the
+    // states as the given permutations; it is a group, the only maximal subgroup
+    fn permutation_monoid(generators: &[Vec<usize>]) -> SyntacticMonoid {
+        let n = generators[0].len();
+        let mut dfa = DFA::new();
+        dfa.alphabet = (0..generators.len()).map(|i| b'a' as usize + i).collect();
+        for q in 0..n {
+            let mut s = State::new(q as i32, q == 0);
+            for (i, g) in generators.iter().enumerate() {
+                s.add_trans(g[q] as i32, b'a' as usize + i);
+            }
+            dfa.add_state(s);
+        }
+        let mut sm = SyntacticMonoid::new();
+        sm.construct(&dfa.minimize());
+        sm
+    }
+
+    fn describe(sm: &SyntacticMonoid) -> (Option<String>, usize, bool, bool, bool) {
+        let green = GreenRelations::new(sm);
+        let groups = MaximalSubgroup::representatives(sm, &green);
+        assert_eq!(groups.len(), 1);
+        let g = &groups[0];
+        (
+            g.name(),
+            g.order(),
+            g.is_abelian(),
+            g.is_cyclic(),
+            g.is_solvable(),
+        )
+    }
+
+    #[test]
+    fn factorization() {
+        assert_eq!(factorize(1), vec![]);
+        assert_eq!(factorize(12), vec![(2, 2), (3, 1)]);
+        assert_eq!(factorize(120), vec![(2, 3), (3, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn abelian_groups() {
+        let cases = vec![
+            (vec![vec![1, 0]], "Z_2"),
+            (vec![vec![1, 0, 3, 2], vec![2, 3, 0, 1]], "Z_2 × Z_2"),
+            (
+                vec![
+                    (0..8).map(|q| (q + 4) % 8).collect(),
+                    (0..8).map(|q| q / 4 * 4 + (q % 4 + 1) % 4).collect(),
+                ],
+                "Z_2 × Z_4",
+            ),
+            (
+                vec![
+                    (0..12).map(|q| (q + 4) % 12).collect(),
+                    (0..12).map(|q| q / 4 * 4 + (q % 4 + 1) % 4).collect(),
+                ],
+                "Z_12",
+            ),
+        ];
+        for (generators, name) in cases {
+            let (found, _, abelian, _, solvable) = describe(&permutation_monoid(&generators));
+            assert_eq!(found, Some(name.to_string()));
+            assert!(abelian && solvable);
+        }
+    }
+
+    #[test]
+    fn symmetric_and_alternating_groups() {
+        let s3 = permutation_monoid(&[vec![1, 2, 0], vec![1, 0, 2]]);
+        assert_eq!(
+            describe(&s3),
+            (Some("S_3".to_string()), 6, false, false, true)
+        );
+        let a4 = permutation_monoid(&[vec![1, 2, 0, 3], vec![1, 0, 3, 2]]);
+        assert_eq!(
+            describe(&a4),
+            (Some("A_4".to_string()), 12, false, false, true)
+        );
+        let a5 = permutation_monoid(&[vec![1, 2, 3, 4, 0], vec![1, 2, 0, 3, 4]]);
+        assert_eq!(
+            describe(&a5),
+            (Some("A_5".to_string()), 60, false, false, false)
+        );
+        let s5 = permutation_monoid(&[vec![1, 2, 3, 4, 0], vec![1, 0, 2, 3, 4]]);
+        assert_eq!(
+            describe(&s5),
+            (Some("S_5".to_string()), 120, false, false, false)
+        );
+    }
+
+    #[test]
+    fn dihedral_groups() {
+        let d4 = permutation_monoid(&[vec![1, 2, 3, 0], vec![0, 3, 2, 1]]);
+        assert_eq!(describe(&d4).0, Some("D_4".to_string()));
+        let d5 = permutation_monoid(&[vec![1, 2, 3, 4, 0], vec![0, 4, 3, 2, 1]]);
+        assert_eq!(describe(&d5).0, Some("D_5".to_string()));
+    }
+
+    #[test]
+    fn cycles() {
+        let sm = permutation_monoid(&[vec![1, 2, 0], vec![1, 0, 2]]);
+        let green = GreenRelations::new(&sm);
+        let g = MaximalSubgroup::new(&sm, &green, 0);
+        let (a, b) = (sm.letter(b'a').unwrap(), sm.letter(b'b').unwrap());
+        assert_eq!(g.cycles(a), "(0 1 2)");
+        assert_eq!(g.cycles(b), "(0 1)");
+        assert_eq!(g.cycles(0), "()");
+        assert_eq!(g.disjoint_cycles(a), vec![vec![0, 1, 2]]);
+        assert!(g.is_even(a) && !g.is_even(b) && g.is_even(0));
+        assert!(!g.is_even(sm.multiply(a, b)));
+        assert_eq!((g.element_order(a), g.element_order(b)), (3, 2));
+        assert_eq!(g.generators(), vec![a, b]);
+        assert_eq!(g.points(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn aperiodic_monoid_has_trivial_groups() {
+        let sm = monoid("(ab)*", "ab");
+        let green = GreenRelations::new(&sm);
+        let groups = MaximalSubgroup::representatives(&sm, &green);
+        assert_eq!(groups.len(), 3);
+        for g in groups {
+            assert_eq!((g.order(), g.name()), (1, Some("trivial".to_string())));
+        }
+    }
+}
@@ -0,0 +1,442 @@
+use order::SyntacticOrder;
+use regex::Parser;
+use std::fmt;
+use syntactic_monoid::{ElemType, SyntacticMonoid};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    One,
+    Var(char),
+    Concat(Vec<Term>),
+    Power(Box<Term>, usize),
+    Omega(Box<Term>), // the idempotent power
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    Equal,
+    LessOrEqual, // in the syntactic order
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    pub left: Term,
+    pub relation: Relation,
+    pub right: Term,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityErrorKind {
+    UnexpectedEnd,
+    UnterminatedGroup,
+    UnmatchedParen,
+    EmptyGroup,
+    InvalidVariable,
+    InvalidExponent,
+    InvalidRelation,
+    TrailingInput,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentityError {
+    pub kind: IdentityErrorKind,
+    pub pos: usize, // byte offset into the identity
+    pub expected: Vec<&'static str>,
+}
+
+impl fmt::Display for IdentityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self.kind {
+            IdentityErrorKind::UnexpectedEnd => "unexpected end of identity",
+            IdentityErrorKind::UnterminatedGroup => "unterminated (",
+            IdentityErrorKind::UnmatchedParen => "unmatched )",
+            IdentityErrorKind::EmptyGroup => "empty group",
+            IdentityErrorKind::InvalidVariable => "invalid variable",
+            IdentityErrorKind::InvalidExponent => "invalid exponent",
+            IdentityErrorKind::InvalidRelation => "invalid relation",
+            IdentityErrorKind::TrailingInput => "trailing input",
+        };
+        write!(f, "{} at {}", msg, self.pos)?;
+        if !self.expected.is_empty() {
+            write!(f, ", expected {}", self.expected.join(" or "))?;
+        }
+        Ok(())
+    }
+}
+
+// the values of the variables of an identity, in the order of `Identity::variables`
+pub type Assignment = Vec<(char, ElemType)>;
+
+/*
+ *
+ * <identity> ::= <term> ( "=" | "≤" | "<=" ) <term>
+ * <term> ::= <power> | <power> <term>
+ * <power> ::= <atom> | <power> "^" ( "ω" | "w" | <number> )
+ * <atom> ::= <variable> | "1" | "(" <term> ")"
+ */
+
+impl Term {
+    fn collect_variables(&self, vars: &mut Vec<char>) {
+        match *self {
+            Term::One => {}
+            Term::Var(x) => vars.push(x),
+            Term::Concat(ref parts) => {
+                for t in parts {
+                    t.collect_variables(vars);
+                }
+            }
+            Term::Power(ref t, _) | Term::Omega(ref t) => t.collect_variables(vars),
+        }
+    }
+
+    pub fn evaluate(&self, monoid: &SyntacticMonoid, assignment: &Assignment) -> ElemType {
+        match *self {
+            Term::One => 0,
+            Term::Var(x) => assignment.iter().find(|v| v.0 == x).unwrap().1,
+            Term::Concat(ref parts) => parts.iter().fold(0, |acc, t| {
+                monoid.multiply(acc, t.evaluate(monoid, assignment))
+            }),
+            Term::Power(ref t, n) => monoid.power(t.evaluate(monoid, assignment), n),
+            Term::Omega(ref t) => monoid.omega(t.evaluate(monoid, assignment)),
+        }
+    }
+}
+
+fn write_power_operand(f: &mut fmt::Formatter, t: &Term) -> fmt::Result {
+    match *t {
+        Term::Concat(_) | Term::Power(_, _) | Term::Omega(_) => write!(f, "({})", t),
+        _ => write!(f, "{}", t),
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Term::One => write!(f, "1"),
+            Term::Var(x) => write!(f, "{}", x),
+            Term::Concat(ref parts) => {
+                for t in parts {
+                    write!(f, "{}", t)?;
+                }
+                Ok(())
+            }
+            Term::Power(ref t, n) => {
+                write_power_operand(f, t)?;
+                write!(f, "^{}", n)
+            }
+            Term::Omega(ref t) => {
+                write_power_operand(f, t)?;
+                write!(f, "^ω")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let relation = match self.relation {
+            Relation::Equal => "=",
+            Relation::LessOrEqual => "≤",
+        };
+        write!(f, "{} {} {}", self.left, relation, self.right)
+    }
+}
+
+impl Identity {
+    // the variables in order of first occurrence
+    pub fn variables(&self) -> Vec<char> {
+        let mut vars = Vec::new();
+        self.left.collect_variables(&mut vars);
+        self.right.collect_variables(&mut vars);
+        let mut seen = Vec::new();
+        for x in vars {
+            if !seen.contains(&x) {
+                seen.push(x);
+            }
+        }
+        seen
+    }
+
+    // evaluates both sides under every assignment of the variables, returning
+    // the first assignment on which they are not related
+    pub fn check(&self, monoid: &SyntacticMonoid) -> Result<(), Assignment> {
+        let order = match self.relation {
+            Relation::Equal => None,
+            Relation::LessOrEqual => Some(SyntacticOrder::new(monoid)),
+        };
+        let vars = self.variables();
+        let mut values = vec![0; vars.len()];
+        loop {
+            let assignment: Assignment = vars.iter().cloned().zip(values.clone()).collect();
+            let left = self.left.evaluate(monoid, &assignment);
+            let right = self.right.evaluate(monoid, &assignment);
+            let related = match order {
+                None => left == right,
+                Some(ref order) => order.less_or_equal(left, right),
+            };
+            if !related {
+                return Err(assignment);
+            }
+            // next assignment, counting in base `size`
+            let mut i = values.len();
+            loop {
+                if i == 0 {
+                    return Ok(());
+                }
+                i -= 1;
+                values[i] += 1;
+                if values[i] < monoid.size() {
+                    break;
+                }
+                values[i] = 0;
+            }
+        }
+    }
+}
+
+pub struct IdentityParser {
+    parser: Parser,
+}
+
+impl Default for IdentityParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdentityParser {
+    pub fn new() -> Self {
+        IdentityParser {
+            parser: Parser::new(),
+        }
+    }
+
+    pub fn parse(&mut self, text: &str) -> Result<Identity, IdentityError> {
+        self.parser.reset(text);
+        let left = self.read_term()?;
+        let relation = match self.peek() {
+            Some('=') => Relation::Equal,
+            Some('≤') => Relation::LessOrEqual,
+            Some('<') => {
+                let less = self.parser.position();
+                self.parser.bump();
+                if self.parser.peek() != Some('=') {
+                    return Err(self.error_at(
+                        less,
+                        IdentityErrorKind::InvalidRelation,
+                        vec!["'<='"],
+                    ));
+                }
+                Relation::LessOrEqual
+            }
+            Some(')') => {
+                return Err(self.error(
+                    IdentityErrorKind::UnmatchedParen,
+                    vec!["variable", "'('", "'^'", "'='", "'≤'"],
+                ));
+            }
+            _ => {
+                return Err(self.error(
+                    IdentityErrorKind::UnexpectedEnd,
+                    vec!["variable", "'('", "'^'", "'='", "'≤'"],
+                ));
+            }
+        };
+        self.parser.bump();
+        let right = self.read_term()?;
+        match self.peek() {
+            None => Ok(Identity {
+                left,
+                relation,
+                right,
+            }),
+            Some(')') => {
+                Err(self.error(IdentityErrorKind::UnmatchedParen, vec!["end of identity"]))
+            }
+            Some(_) => Err(self.error(IdentityErrorKind::TrailingInput, vec!["end of identity"])),
+        }
+    }
+
+    fn error(&self, kind: IdentityErrorKind, expected: Vec<&'static str>) -> IdentityError {
+        self.error_at(self.parser.position(), kind, expected)
+    }
+
+    fn error_at(
+        &self,
+        cur: usize,
+        kind: IdentityErrorKind,
+        expected: Vec<&'static str>,
+    ) -> IdentityError {
+        IdentityError {
+            kind,
+            pos: self.parser.offset(cur),
+            expected,
+        }
+    }
+
+    // skips whitespace, which carries no meaning in identities
+    fn peek(&mut self) -> Option<char> {
+        while self.parser.peek().is_some_and(char::is_whitespace) {
+            self.parser.bump();
+        }
+        self.parser.peek()
+    }
+
+    fn read_term(&mut self) -> Result<Term, IdentityError> {
+        let mut parts = Vec::new();
+        while self.peek().is_some_and(|c| !")=≤<".contains(c)) {
+            parts.push(self.read_power()?);
+        }
+        match parts.len() {
+            0 => match self.peek() {
+                None => Err(self.error(IdentityErrorKind::UnexpectedEnd, vec!["variable", "'('"])),
+                Some(')') => {
+                    Err(self.error(IdentityErrorKind::UnmatchedParen, vec!["variable", "'('"]))
+                }
+                Some(_) => {
+                    Err(self.error(IdentityErrorKind::InvalidVariable, vec!["variable", "'('"]))
+                }
+            },
+            1 => Ok(parts.pop().unwrap()),
+            _ => Ok(Term::Concat(parts)),
+        }
+    }
+
+    fn read_power(&mut self) -> Result<Term, IdentityError> {
+        let mut term = self.read_atom()?;
+        while self.peek() == Some('^') {
+            self.parser.bump();
+            term = match self.peek() {
+                Some('ω') | Some('w') => {
+                    self.parser.bump();
+                    Term::Omega(Box::new(term))
+                }
+                _ => match self.parser.read_number() {
+                    Some(n) => Term::Power(Box::new(term), n),
+                    None => {
+                        return Err(
+                            self.error(IdentityErrorKind::InvalidExponent, vec!["'ω'", "number"])
+                        );
+                    }
+                },
+            };
+        }
+        Ok(term)
+    }
+
+    fn read_atom(&mut self) -> Result<Term, IdentityError> {
+        match self.peek() {
+            Some('(') => {
+                let open = self.parser.position();
+                self.parser.bump();
+                if self.peek() == Some(')') {
+                    return Err(self.error(IdentityErrorKind::EmptyGroup, vec!["variable", "'('"]));
+                }
+                let term = self.read_term()?;
+                if self.peek() != Some(')') {
+                    return Err(self.error_at(
+                        open,
+                        IdentityErrorKind::UnterminatedGroup,
+                        vec!["')'"],
+                    ));
+                }
+                self.parser.bump();
+                Ok(term)
+            }
+            Some('1') => {
+                self.parser.bump();
+                Ok(Term::One)
+            }
+            Some(x) if x.is_ascii_alphabetic() => {
+                self.parser.bump();
+                Ok(Term::Var(x))
+            }
+            _ => Err(self.error(IdentityErrorKind::InvalidVariable, vec!["variable", "'('"])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntactic_monoid::tests::monoid;
+
+    fn error(text: &str) -> (IdentityErrorKind, usize) {
+        let e = IdentityParser::new().parse(text).unwrap_err();
+        (e.kind, e.pos)
+    }
+
+    #[test]
+    fn parse() {
+        let identity = IdentityParser::new().parse("(xy)^ω x <= 1 y^2").unwrap();
+        assert_eq!(identity.relation, Relation::LessOrEqual);
+        assert_eq!(identity.variables(), vec!['x', 'y']);
+        assert_eq!(identity.to_string(), "(xy)^ωx ≤ 1y^2");
+        let identity = IdentityParser::new().parse("x^w=x^ω").unwrap();
+        assert_eq!(identity.left, Term::Omega(Box::new(Term::Var('x'))));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error(""), (IdentityErrorKind::UnexpectedEnd, 0));
+        assert_eq!(error("x"), (IdentityErrorKind::UnexpectedEnd, 1));
+        assert_eq!(error("x ="), (IdentityErrorKind::UnexpectedEnd, 3));
+        assert_eq!(error("x) = y"), (IdentityErrorKind::UnmatchedParen, 1));
+        assert_eq!(error(")x = y"), (IdentityErrorKind::UnmatchedParen, 0));
+        assert_eq!(error("x = y)"), (IdentityErrorKind::UnmatchedParen, 5));
+        assert_eq!(error("(x = y"), (IdentityErrorKind::UnterminatedGroup, 0));
+        assert_eq!(error("() = y"), (IdentityErrorKind::EmptyGroup, 1));
+        assert_eq!(error("x = 2"), (IdentityErrorKind::InvalidVariable, 4));
+        assert_eq!(error("= y"), (IdentityErrorKind::InvalidVariable, 0));
+        assert_eq!(error("x^ = y"), (IdentityErrorKind::InvalidExponent, 3));
+        assert_eq!(error("x < y"), (IdentityErrorKind::InvalidRelation, 2));
+        assert_eq!(error("x = y = z"), (IdentityErrorKind::TrailingInput, 6));
+        // positions are byte offsets
+        assert_eq!(error("x^ω = ω"), (IdentityErrorKind::InvalidVariable, 7));
+    }
+
+    #[test]
+    fn check() {
+        let aperiodic = IdentityParser::new().parse("x^ω x = x^ω").unwrap();
+        let commutative = IdentityParser::new().parse("xy = yx").unwrap();
+        let sm = monoid("(ab)*", "ab");
+        assert_eq!(aperiodic.check(&sm), Ok(()));
+        assert!(commutative.check(&sm).is_err());
+        let sm = monoid("(aa)*", "a");
+        assert_eq!(
+            aperiodic.check(&sm),
+            Err(vec![('x', sm.letter(b'a').unwrap())])
+        );
+        assert_eq!(commutative.check(&sm), Ok(()));
+    }
+
+    #[test]
+    fn large_powers() {
+        // the powers of a in (aaa)* repeat with period 3, and 10^11 = 1 mod 3
+        let sm = monoid("(aaa)*", "a");
+        let identity = IdentityParser::new().parse("x^100000000000 = x").unwrap();
+        assert_eq!(identity.check(&sm), Ok(()));
+        let identity = IdentityParser::new().parse("x^99999999999 = x").unwrap();
+        assert_eq!(
+            identity.check(&sm),
+            Err(vec![('x', sm.letter(b'a').unwrap())])
+        );
+        let a = sm.letter(b'a').unwrap();
+        assert_eq!(sm.power(a, 0), 0);
+        assert_eq!(sm.power(a, 4), a);
+        assert_eq!(sm.power(a, 3), sm.multiply(a, sm.multiply(a, a)));
+    }
+
+    #[test]
+    fn check_order() {
+        // a* over {a, b} is closed under deleting letters but not under inserting b
+        let sm = monoid("a*", "ab");
+        let above_one = IdentityParser::new().parse("1 ≤ x").unwrap();
+        assert_eq!(above_one.check(&sm), Ok(()));
+        let below_one = IdentityParser::new().parse("x ≤ 1").unwrap();
+        assert_eq!(
+            below_one.check(&sm),
+            Err(vec![('x', sm.letter(b'b').unwrap())])
+        );
+    }
+}
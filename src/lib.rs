@@ -0,0 +1,16 @@
+#![allow(clippy::upper_case_acronyms)]
+
+extern crate bit_set;
+
+pub mod cayley;
+pub mod dfa;
+pub mod green;
+pub mod group;
+pub mod identity;
+pub mod nfa;
+pub mod order;
+pub mod presentation;
+pub mod regex;
+pub mod starfree;
+pub mod syntactic_monoid;
+pub mod variety;
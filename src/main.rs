@@ -1,47 +1,360 @@
 use std::env;
+use std::fmt;
 
-mod regex;
-mod nfa;
-mod dfa;
-mod syntactic_monoid;
-use regex::Parser;
-use nfa::NFA;
-use dfa::DFA;
-use syntactic_monoid::SyntacticMonoid;
+extern crate bit_set;
+extern crate syntactic_monoid;
+use bit_set::BitSet;
+use syntactic_monoid::cayley;
+use syntactic_monoid::cayley::Side;
+use syntactic_monoid::dfa::DFA;
+use syntactic_monoid::green::GreenRelations;
+use syntactic_monoid::group;
+use syntactic_monoid::identity::IdentityParser;
+use syntactic_monoid::nfa::NFA;
+use syntactic_monoid::order::SyntacticOrder;
+use syntactic_monoid::presentation::Presentation;
+use syntactic_monoid::regex::{class_to_string, word_to_string, Parser, RegularExpression};
+use syntactic_monoid::syntactic_monoid::SyntacticMonoid;
+use syntactic_monoid::variety;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let input = if args.len() >= 2 {
-        args[1].clone()
-    } else {
-        "(a|ba)*".to_string()
-    };
+// `pos` is the byte offset of the error in `input`
+fn print_parse_error(e: &dyn fmt::Display, pos: usize, input: &str) {
+    println!("parse error: {}", e);
+    println!("  {}", input);
+    println!("  {}^", " ".repeat(input[..pos].chars().count()));
+}
+
+fn parse_pattern(parser: &mut Parser, input: &str) -> Option<RegularExpression> {
+    match parser.parse(input) {
+        Ok(re) => Some(re),
+        Err(e) => {
+            print_parse_error(&e, e.pos, input);
+            None
+        }
+    }
+}
+
+fn parse_alphabet(parser: &mut Parser, letters: &str) -> Option<BitSet> {
+    match parser.parse_alphabet(letters) {
+        Ok(alphabet) => Some(alphabet),
+        Err(e) => {
+            print_parse_error(&e, e.pos, letters);
+            None
+        }
+    }
+}
+
+fn minimal_dfa(re: &RegularExpression, alphabet: &BitSet) -> DFA {
+    DFA::construct_from_nfa(&NFA::construct(re, alphabet)).minimize()
+}
+
+// compare <pattern1> <pattern2>: language equivalence and inclusion
+fn compare(patterns: &[String], alphabet: Option<String>) {
+    if patterns.len() != 2 {
+        println!("usage: syntactic_monoid compare [--alphabet <letters>] <pattern1> <pattern2>");
+        return;
+    }
     let mut parser = Parser::new();
-    let re = match parser.parse(&input) {
+    let re1 = match parse_pattern(&mut parser, &patterns[0]) {
         Some(re) => re,
+        None => return,
+    };
+    let re2 = match parse_pattern(&mut parser, &patterns[1]) {
+        Some(re) => re,
+        None => return,
+    };
+    let alphabet = match alphabet {
+        Some(letters) => match parse_alphabet(&mut parser, &letters) {
+            Some(alphabet) => alphabet,
+            None => return,
+        },
         None => {
-            println!("parse error");
+            let mut alphabet = re1.alphabet();
+            alphabet.union_with(&re2.alphabet());
+            alphabet
+        }
+    };
+    println!("L1 = {}", re1);
+    println!("L2 = {}", re2);
+    println!("alphabet: {}", class_to_string(&alphabet));
+
+    let dfa1 = minimal_dfa(&re1, &alphabet);
+    let dfa2 = minimal_dfa(&re2, &alphabet);
+    match dfa1.is_subset_of(&dfa2) {
+        Ok(()) => println!("L1 ⊆ L2"),
+        Err(word) => println!("L1 ⊈ L2: {} is in L1 only", word_to_string(&word)),
+    }
+    match dfa2.is_subset_of(&dfa1) {
+        Ok(()) => println!("L2 ⊆ L1"),
+        Err(word) => println!("L2 ⊈ L1: {} is in L2 only", word_to_string(&word)),
+    }
+    match dfa1.equivalent(&dfa2) {
+        Ok(()) => println!("equivalent"),
+        Err(word) => println!("not equivalent: they differ on {}", word_to_string(&word)),
+    }
+}
+
+// congruent <pattern> <u> <v>: whether u and v are syntactically congruent
+fn congruent(args: &[String], alphabet: Option<String>) {
+    if args.len() != 3 {
+        println!("usage: syntactic_monoid congruent [--alphabet <letters>] <pattern> <u> <v>");
+        return;
+    }
+    let mut parser = Parser::new();
+    let re = match parse_pattern(&mut parser, &args[0]) {
+        Some(re) => re,
+        None => return,
+    };
+    let alphabet = match alphabet {
+        Some(letters) => match parse_alphabet(&mut parser, &letters) {
+            Some(alphabet) => alphabet,
+            None => return,
+        },
+        None => re.alphabet(),
+    };
+    println!("L = {}", re);
+    println!("alphabet: {}", class_to_string(&alphabet));
+
+    let min_dfa = minimal_dfa(&re, &alphabet);
+    let mut sm = SyntacticMonoid::new();
+    sm.construct(&min_dfa);
+    let (u, v) = (args[1].as_bytes(), args[2].as_bytes());
+    let (s, t) = match (sm.morphism(u), sm.morphism(v)) {
+        (Ok(s), Ok(t)) => (s, t),
+        (Err(e), _) | (_, Err(e)) => {
+            println!("error: {}", e);
             return;
         }
     };
+    println!("{} ↦ {}", word_to_string(u), sm.label(s));
+    println!("{} ↦ {}", word_to_string(v), sm.label(t));
+    match sm.separating_context(s, t) {
+        None => println!("{} ≡ {}", word_to_string(u), word_to_string(v)),
+        Some((x, y)) => {
+            let in_language = |w: &[u8]| {
+                let word: Vec<u8> = x.iter().chain(w).chain(&y).cloned().collect();
+                min_dfa.accept(&word)
+            };
+            println!(
+                "{} ≢ {}: separated by the context x = {}, y = {} ({} is {}in L, {} is {}in L)",
+                word_to_string(u),
+                word_to_string(v),
+                word_to_string(&x),
+                word_to_string(&y),
+                word_to_string(&[&x[..], u, &y[..]].concat()),
+                if in_language(u) { "" } else { "not " },
+                word_to_string(&[&x[..], v, &y[..]].concat()),
+                if in_language(v) { "" } else { "not " },
+            );
+        }
+    }
+}
+
+fn main() {
+    let mut verify = false;
+    let mut matrices = false;
+    let mut powers = false;
+    let mut eggbox = false;
+    let mut eggbox_dot = false;
+    let mut j_order = false;
+    let mut groups = false;
+    let mut order = false;
+    let mut varieties = false;
+    let mut presentation = false;
+    let mut cayley: Option<String> = None;
+    let mut collapse = false;
+    let mut identities: Vec<String> = Vec::new();
+    let mut alphabet: Option<String> = None;
+    let mut patterns: Vec<String> = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--verify" => verify = true,
+            "--matrices" => matrices = true,
+            "--powers" => powers = true,
+            "--eggbox" => eggbox = true,
+            "--eggbox-dot" => eggbox_dot = true,
+            "--j-order" => j_order = true,
+            "--groups" => groups = true,
+            "--order" => order = true,
+            "--varieties" => varieties = true,
+            "--presentation" => presentation = true,
+            "--collapse" => collapse = true,
+            "--cayley" | "--identity" | "--alphabet" => {
+                let value = match args.next() {
+                    Some(value) => value,
+                    None => {
+                        println!("{} expects a value", arg);
+                        return;
+                    }
+                };
+                match arg.as_str() {
+                    "--cayley" => cayley = Some(value),
+                    "--identity" => identities.push(value),
+                    _ => alphabet = Some(value),
+                }
+            }
+            _ if arg.starts_with("--") => {
+                println!("unknown option {}", arg);
+                return;
+            }
+            _ => patterns.push(arg),
+        }
+    }
+    if patterns.first().map(|p| p.as_str()) == Some("compare") {
+        compare(&patterns[1..], alphabet);
+        return;
+    }
+    if patterns.first().map(|p| p.as_str()) == Some("congruent") {
+        congruent(&patterns[1..], alphabet);
+        return;
+    }
+    if patterns.len() > 1 {
+        println!("unexpected argument {}", patterns[1]);
+        return;
+    }
+    let input = patterns.pop().unwrap_or_else(|| "(a|ba)*".to_string());
+    let mut parser = Parser::new();
+    let re = match parse_pattern(&mut parser, &input) {
+        Some(re) => re,
+        None => return,
+    };
+    let alphabet = match alphabet {
+        Some(letters) => match parse_alphabet(&mut parser, &letters) {
+            Some(alphabet) => alphabet,
+            None => return,
+        },
+        None => re.alphabet(),
+    };
     println!("regular expression:");
-    println!("{}", re.to_string());
+    println!("{}", re);
+    println!("alphabet: {}", class_to_string(&alphabet));
 
-    let nfa = NFA::construct(&re);
-    let dfa = DFA::construct_from_nfa(&nfa);
-    let min_dfa = dfa.minimize();
+    let min_dfa = minimal_dfa(&re, &alphabet);
     println!("minimized dfa:");
     min_dfa.to_graphviz();
+    println!("dfa size:{}", min_dfa.size());
     let mut sm = SyntacticMonoid::new();
 
-    sm.construct(&min_dfa, &input);
+    sm.construct(&min_dfa);
+    println!("elements:");
+    sm.print_elements(matrices);
+    if powers {
+        println!("powers:");
+        sm.print_power_table();
+    }
+    if eggbox || eggbox_dot || j_order {
+        let green = GreenRelations::new(&sm);
+        if eggbox {
+            println!("egg-box diagram:");
+            green.print_eggbox();
+        }
+        if eggbox_dot {
+            green.eggbox_to_graphviz();
+        }
+        if j_order {
+            green.j_order_to_graphviz();
+            let minimal = &green.d_classes[green.minimal_ideal()];
+            let words: Vec<String> = minimal.elements.iter().map(|e| sm.label(*e)).collect();
+            println!("minimal ideal: {}", words.join(" "));
+            match green.zero() {
+                Some(z) => println!("zero: {}", sm.label(z)),
+                None => println!("zero: none"),
+            }
+        }
+    }
+    if groups {
+        println!("maximal subgroups:");
+        group::print_maximal_subgroups(&sm);
+    }
+    if order {
+        SyntacticOrder::new(&sm).to_graphviz();
+    }
+    match cayley.as_deref() {
+        Some("right") => cayley::to_graphviz(&sm, Side::Right, collapse),
+        Some("left") => cayley::to_graphviz(&sm, Side::Left, collapse),
+        Some(_) => println!("--cayley expects right or left"),
+        None => {}
+    }
+    if presentation {
+        let p = Presentation::new(&sm);
+        println!("presentation:");
+        println!("{}", p);
+        println!("{}", p.to_latex());
+        if p.rebuilds(&sm) {
+            println!("presentation check: isomorphic");
+        } else {
+            println!("presentation check: failed");
+        }
+    }
+    if varieties {
+        println!("varieties:");
+        variety::print_report(&sm);
+    }
+    for text in &identities {
+        let identity = match IdentityParser::new().parse(text) {
+            Ok(identity) => identity,
+            Err(e) => {
+                print_parse_error(&e, e.pos, text);
+                continue;
+            }
+        };
+        match identity.check(&sm) {
+            Ok(()) => println!("identity {}: holds", identity),
+            Err(assignment) => {
+                let values: Vec<String> = assignment
+                    .iter()
+                    .map(|&(x, e)| format!("{} = {}", x, sm.label(e)))
+                    .collect();
+                println!("identity {}: fails for {}", identity, values.join(", "));
+            }
+        }
+    }
     match sm.starfree_expression() {
         Some(exp) => {
             println!("starfree expression:");
             println!("{}", exp);
+            if verify {
+                match exp.to_dfa(&alphabet).equivalent(&min_dfa) {
+                    Ok(()) => println!("verification: equivalent"),
+                    Err(word) => println!(
+                        "verification: differ on {} (accepted by {})",
+                        word_to_string(&word),
+                        if min_dfa.accept(&word) {
+                            "the input only"
+                        } else {
+                            "the star-free expression only"
+                        }
+                    ),
+                }
+            }
         }
         None => {
-            println!("the monoid is aperiodic");
+            let w = sm.non_aperiodic_witness().unwrap();
+            println!("the monoid is not aperiodic, so the language is not star-free:");
+            println!(
+                "  u = {} has index {} and period {}",
+                word_to_string(&w.u),
+                w.index,
+                w.period
+            );
+            // x u^n y for the least n >= index divisible by the period
+            let n = w.index.div_ceil(w.period) * w.period;
+            let mut word = w.x.clone();
+            for _ in 0..n {
+                word.extend(&w.u);
+            }
+            word.extend(&w.y);
+            let accepted = min_dfa.accept(&word);
+            println!(
+                "  x = {}, y = {}: for every n >= {} divisible by {}, x u^n y is {} and x u^(n+1) y is {}",
+                word_to_string(&w.x),
+                word_to_string(&w.y),
+                w.index,
+                w.period,
+                if accepted { "accepted" } else { "rejected" },
+                if accepted { "rejected" } else { "accepted" }
+            );
         }
     }
 }
@@ -1,5 +1,7 @@
+use bit_set::BitSet;
+use dfa::DFA;
 use regex;
-use regex::RegularExpression;
+use regex::{full_alphabet, RegularExpression};
 use std::collections::BTreeSet;
 use std::collections::VecDeque;
 
@@ -18,8 +20,8 @@ impl State {
         State {
             ts: vec![BTreeSet::new(); 256],
             epsilon: BTreeSet::new(),
-            id: id,
-            accept: accept,
+            id,
+            accept,
         }
     }
 
@@ -38,7 +40,7 @@ impl State {
             for q in t.iter() {
                 print!("{} ", *q);
             }
-            println!("");
+            println!();
         }
     }
 }
@@ -48,6 +50,13 @@ pub struct NFA {
     pub states: Vec<State>,
     pub start: State,
     pub end: State,
+    pub alphabet: BitSet,
+}
+
+impl Default for NFA {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NFA {
@@ -56,6 +65,7 @@ impl NFA {
             states: Vec::new(),
             start: State::new(0, false),
             end: State::new(0, false),
+            alphabet: full_alphabet(),
         }
     }
     pub fn size(&self) -> usize {
@@ -117,12 +127,57 @@ impl NFA {
         nfa
     }
 
-    pub fn construct(re: &regex::RegularExpression) -> Self {
+    pub fn from_dfa(dfa: &DFA) -> Self {
+        let snum = dfa.size();
+        let mut s_i = State::new(0, false);
+        if snum > 0 {
+            s_i.add_epsilon(dfa.start + 1);
+        }
+        let s_f = State::new(snum + 1, true);
+        let mut nfa = NFA::new();
+        nfa.add_state(s_i.clone());
+        for (i, s) in dfa.states.iter().enumerate() {
+            let mut q = State::new(i + 1, false);
+            for ch in dfa.alphabet.iter() {
+                let t = s.transition(ch as u8);
+                if t != -1 {
+                    q.add_trans(t as usize + 1, ch);
+                }
+            }
+            if s.accept {
+                q.add_epsilon(s_f.id);
+            }
+            nfa.add_state(q);
+        }
+        nfa.add_state(s_f.clone());
+        nfa.start = s_i;
+        nfa.end = s_f;
+        nfa.alphabet = dfa.alphabet.clone();
+        nfa
+    }
+
+    // Thompson's construction of an automaton for the words over `alphabet` matching `re`
+    pub fn construct(re: &regex::RegularExpression, alphabet: &BitSet) -> Self {
+        let mut nfa = NFA::build(re, alphabet);
+        nfa.alphabet = alphabet.clone();
+        nfa
+    }
+
+    fn build(re: &regex::RegularExpression, alphabet: &BitSet) -> Self {
         match *re {
-            RegularExpression::Empty => NFA::new(),
+            RegularExpression::Empty => {
+                let s_i = State::new(0, false);
+                let s_f = State::new(1, true);
+                let mut nfa = NFA::new();
+                nfa.add_state(s_i.clone());
+                nfa.add_state(s_f.clone());
+                nfa.start = s_i;
+                nfa.end = s_f;
+                nfa
+            }
             RegularExpression::Epsilon => {
                 let mut s_i = State::new(0, false);
-                let mut s_f = State::new(1, true);
+                let s_f = State::new(1, true);
                 s_i.add_epsilon(1);
                 let mut nfa = NFA::new();
                 nfa.add_state(s_i.clone());
@@ -134,7 +189,9 @@ impl NFA {
             RegularExpression::Char(a) => {
                 let mut s_i = State::new(0, false);
                 let s_f = State::new(1, true);
-                s_i.add_trans(1, a as usize);
+                if alphabet.contains(a as usize) {
+                    s_i.add_trans(1, a as usize);
+                }
                 let mut nfa = NFA::new();
                 nfa.add_state(s_i.clone());
                 nfa.add_state(s_f.clone());
@@ -142,44 +199,28 @@ impl NFA {
                 nfa.end = s_f;
                 nfa
             }
-            RegularExpression::Concat(ref e1, ref e2) => {
-                let mut nfa1 = NFA::construct(e1);
-                let snum1 = nfa1.size();
-                let mut nfa2 = NFA::construct(e2);
-
-                let mut s_merged = nfa1.end.clone();
-                let mut s_i2 = nfa2.start.clone();
-                for (ch, t) in s_i2.ts.iter().enumerate() {
-                    for q in t.iter() {
-                        s_merged.add_trans(*q + snum1 - 1, ch);
-                    }
-                }
-                s_merged.accept = false;
-                for q in s_i2.epsilon.iter() {
-                    s_merged.add_epsilon(*q + snum1 - 1);
+            RegularExpression::Class(ref class, _) => {
+                let mut s_i = State::new(0, false);
+                let s_f = State::new(1, true);
+                for a in class.intersection(alphabet) {
+                    s_i.add_trans(1, a);
                 }
-
-                let nfa2 = nfa2.shift_idx(snum1 - 1);
                 let mut nfa = NFA::new();
-                for s in &nfa1.states {
-                    if s.id != nfa1.end.id {
-                        nfa.add_state(s.clone());
-                    }
-                }
-                nfa.add_state(s_merged.clone());
-                for s in &nfa2.states {
-                    if s.id != nfa2.start.id {
-                        nfa.add_state(s.clone());
-                    }
-                }
-                nfa.start = nfa1.start;
-                nfa.end = nfa2.end;
+                nfa.add_state(s_i.clone());
+                nfa.add_state(s_f.clone());
+                nfa.start = s_i;
+                nfa.end = s_f;
+                nfa
+            }
+            RegularExpression::Concat(ref e1, ref e2) => {
+                let mut nfa = NFA::build(e1, alphabet);
+                nfa.append(&NFA::build(e2, alphabet));
                 nfa
             }
             RegularExpression::Union(ref e1, ref e2) => {
-                let nfa1 = NFA::construct(e1);
+                let nfa1 = NFA::build(e1, alphabet);
                 let snum1 = nfa1.size();
-                let nfa2 = NFA::construct(e2);
+                let nfa2 = NFA::build(e2, alphabet);
                 let snum2 = nfa2.size();
 
                 let mut s_i = State::new(0, false);
@@ -217,13 +258,48 @@ impl NFA {
                 nfa.end = s_f;
                 nfa
             }
+            RegularExpression::Repeat(ref e, min, max) => {
+                // e{m,n} = e^m (e|ε)^(n-m),  e{m,} = e^m e*, chained one copy at a time
+                let mut nfa = NFA::build(&RegularExpression::Epsilon, alphabet);
+                let part = NFA::build(e, alphabet);
+                for _ in 0..min {
+                    nfa.append(&part);
+                }
+                match max {
+                    Some(max) => {
+                        let optional = NFA::build(
+                            &RegularExpression::Union(
+                                e.clone(),
+                                Box::new(RegularExpression::Epsilon),
+                            ),
+                            alphabet,
+                        );
+                        for _ in min..max {
+                            nfa.append(&optional);
+                        }
+                    }
+                    None => {
+                        nfa.append(&NFA::build(&RegularExpression::Kleene(e.clone()), alphabet))
+                    }
+                }
+                nfa
+            }
+            RegularExpression::Intersection(ref e1, ref e2) => {
+                let dfa1 = DFA::construct_from_nfa(&NFA::construct(e1, alphabet)).minimize();
+                let dfa2 = DFA::construct_from_nfa(&NFA::construct(e2, alphabet)).minimize();
+                NFA::from_dfa(&dfa1.intersect(&dfa2))
+            }
+            RegularExpression::Complement(ref e) => {
+                let dfa = DFA::construct_from_nfa(&NFA::construct(e, alphabet)).minimize();
+                NFA::from_dfa(&dfa.complement())
+            }
             RegularExpression::Kleene(ref e) => {
-                let mut nfa = NFA::construct(e);
+                let nfa = NFA::build(e, alphabet);
                 let nfa = nfa.shift_idx(1);
                 let snum = nfa.size();
                 let mut s_i = State::new(0, false);
                 s_i.add_epsilon(nfa.start.id);
-                let mut s_f = State::new(snum + 1, true);
+                let s_f = State::new(snum + 1, true);
                 let mut s_e_i = nfa.start.clone();
                 s_e_i.add_epsilon(s_f.id);
                 let mut s_e_f = nfa.end.clone();
@@ -250,6 +326,30 @@ impl NFA {
         }
     }
 
+    // Concatenates `other` after self by merging the end state of self, which
+    // is always the last state, with the start state of `other`.
+    fn append(&mut self, other: &NFA) {
+        let shift = self.size() - 1;
+        let other = other.shift_idx(shift);
+        let mut merged = self.states.pop().unwrap();
+        for (ch, t) in other.start.ts.iter().enumerate() {
+            for q in t.iter() {
+                merged.add_trans(*q, ch);
+            }
+        }
+        for q in other.start.epsilon.iter() {
+            merged.add_epsilon(*q);
+        }
+        merged.accept = false;
+        self.states.push(merged);
+        for s in other.states {
+            if s.id != other.start.id {
+                self.states.push(s);
+            }
+        }
+        self.end = other.end;
+    }
+
     pub fn to_graphviz(&self) {
         println!("digraph NFA {{");
         println!("  rankdir=\"LR\"");
@@ -280,3 +380,77 @@ impl NFA {
         println!("}}");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Parser;
+
+    // the words up to length `n` over `letters` accepted by the pattern
+    fn language(pattern: &str, letters: &str, n: usize) -> Vec<String> {
+        let re = Parser::new().parse(pattern).unwrap();
+        let alphabet: BitSet = letters.bytes().map(|a| a as usize).collect();
+        let dfa = DFA::construct_from_nfa(&NFA::construct(&re, &alphabet));
+        let mut words = vec![String::new()];
+        let mut last = vec![String::new()];
+        for _ in 0..n {
+            last = last
+                .iter()
+                .flat_map(|w| letters.chars().map(move |a| format!("{}{}", w, a)))
+                .collect();
+            words.extend(last.iter().cloned());
+        }
+        words
+            .into_iter()
+            .filter(|w| dfa.accept(&w.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn exact_repetition() {
+        assert_eq!(language("a{3}", "a", 5), vec!["aaa"]);
+        assert_eq!(language("a{0}", "a", 3), vec![""]);
+        assert_eq!(language("(ab){2}", "ab", 5), vec!["abab"]);
+    }
+
+    #[test]
+    fn unbounded_repetition() {
+        assert_eq!(language("a{2,}", "a", 4), vec!["aa", "aaa", "aaaa"]);
+        assert_eq!(language("(ab){1,}", "ab", 5), vec!["ab", "abab"]);
+    }
+
+    #[test]
+    fn bounded_repetition() {
+        assert_eq!(language("a{1,3}b", "ab", 5), vec!["ab", "aab", "aaab"]);
+        assert_eq!(language("a{0,1}", "a", 3), vec!["", "a"]);
+    }
+
+    #[test]
+    fn intersection() {
+        assert_eq!(language("a*b*&(ab)*", "ab", 4), vec!["", "ab"]);
+        assert_eq!(
+            language("(a|b)*a&b(a|b)*", "ab", 3),
+            vec!["ba", "baa", "bba"]
+        );
+        assert_eq!(language("a&b", "ab", 2), Vec::<String>::new());
+    }
+
+    #[test]
+    fn complement_is_relative_to_the_alphabet() {
+        assert_eq!(language("!(a*)", "a", 3), Vec::<String>::new());
+        assert_eq!(language("!(a*)", "ab", 2), vec!["b", "ab", "ba", "bb"]);
+        assert_eq!(language("!(.*a.*)", "abc", 1), vec!["", "b", "c"]);
+        assert_eq!(language("!@&a", "ab", 2), vec!["a"]);
+        assert_eq!(language("!!(ab)", "abc", 2), vec!["ab"]);
+    }
+
+    #[test]
+    fn largest_repetition() {
+        let re = Parser::new().parse("a{1000}").unwrap();
+        let alphabet: BitSet = [b'a' as usize].iter().cloned().collect();
+        let dfa = DFA::construct_from_nfa(&NFA::construct(&re, &alphabet));
+        assert!(dfa.accept(&vec![b'a'; 1000]));
+        assert!(!dfa.accept(&vec![b'a'; 999]));
+        assert!(!dfa.accept(&vec![b'a'; 1001]));
+    }
+}
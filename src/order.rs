@@ -0,0 +1,133 @@
+use bit_set::BitSet;
+use green::escape_dot;
+use syntactic_monoid::{ElemType, SyntacticMonoid};
+
+// The syntactic order: s <= t iff for every context (x, y), x t y accepted
+// implies x s y accepted. It is compatible with the multiplication and makes
+// the syntactic monoid an ordered monoid.
+pub struct SyntacticOrder<'a> {
+    monoid: &'a SyntacticMonoid,
+    contexts: Vec<BitSet>, // contexts[s]: the contexts (x, y), as x * size + y, with x s y accepted
+}
+
+impl<'a> SyntacticOrder<'a> {
+    pub fn new(monoid: &'a SyntacticMonoid) -> Self {
+        let n = monoid.size();
+        let contexts = (0..n)
+            .map(|s| {
+                let mut c = BitSet::with_capacity(n * n);
+                for x in 0..n {
+                    let xs = monoid.multiply(x, s);
+                    for y in 0..n {
+                        if monoid.accept(&monoid.multiply(xs, y)) {
+                            c.insert(x * n + y);
+                        }
+                    }
+                }
+                c
+            })
+            .collect();
+        SyntacticOrder { monoid, contexts }
+    }
+
+    pub fn less_or_equal(&self, s: ElemType, t: ElemType) -> bool {
+        self.contexts[t].is_subset(&self.contexts[s])
+    }
+
+    // the covering pairs (lower, upper) of the order
+    pub fn hasse(&self) -> Vec<(ElemType, ElemType)> {
+        let n = self.monoid.size();
+        let below = |s: ElemType, t: ElemType| s != t && self.less_or_equal(s, t);
+        let mut covers = Vec::new();
+        for lower in 0..n {
+            for upper in 0..n {
+                if below(lower, upper) && !(0..n).any(|u| below(lower, u) && below(u, upper)) {
+                    covers.push((lower, upper));
+                }
+            }
+        }
+        covers
+    }
+
+    // the Hasse diagram with greater elements on top; accepting elements are
+    // drawn with a double border
+    pub fn to_graphviz(&self) {
+        println!("digraph SyntacticOrder {{");
+        for e in 0..self.monoid.size() {
+            print!(
+                "  {} [ label = \"{}\"",
+                e,
+                escape_dot(&self.monoid.label(e))
+            );
+            if self.monoid.accept(&e) {
+                print!(", peripheries=2");
+            }
+            println!(" ];");
+        }
+        for (lower, upper) in self.hasse() {
+            println!(" {} -> {};", upper, lower);
+        }
+        println!("}}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntactic_monoid::tests::monoid;
+
+    #[test]
+    fn order_matches_the_definition() {
+        for &(pattern, letters) in &[("(a|ba)*", "ab"), ("a*b", "ab"), ("(ab)*", "ab")] {
+            let sm = monoid(pattern, letters);
+            let order = SyntacticOrder::new(&sm);
+            let n = sm.size();
+            // every context is the image of its representative words
+            let accepts = |x: ElemType, e: ElemType, y: ElemType| {
+                let word = [sm.word(x), sm.word(e), sm.word(y)].concat();
+                sm.accept(&sm.morphism(&word).unwrap())
+            };
+            for s in 0..n {
+                for t in 0..n {
+                    let below =
+                        (0..n).all(|x| (0..n).all(|y| !accepts(x, t, y) || accepts(x, s, y)));
+                    assert_eq!(order.less_or_equal(s, t), below, "{}", pattern);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn partial_order_compatible_with_multiplication() {
+        let sm = monoid("(a|ba)*", "ab");
+        let order = SyntacticOrder::new(&sm);
+        let n = sm.size();
+        for s in 0..n {
+            assert!(order.less_or_equal(s, s));
+            for t in 0..n {
+                if s != t && order.less_or_equal(s, t) {
+                    assert!(!order.less_or_equal(t, s));
+                    for u in 0..n {
+                        assert!(order.less_or_equal(sm.multiply(s, u), sm.multiply(t, u)));
+                        assert!(order.less_or_equal(sm.multiply(u, s), sm.multiply(u, t)));
+                        if order.less_or_equal(t, u) {
+                            assert!(order.less_or_equal(s, u));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hasse() {
+        // inserting b into a word of a* leaves the language, deleting it does not
+        let sm = monoid("a*", "ab");
+        let order = SyntacticOrder::new(&sm);
+        let b = sm.letter(b'b').unwrap();
+        assert_eq!(order.hasse(), vec![(0, b)]);
+        // in a group the order is trivial
+        let sm = monoid("(aa)*", "a");
+        assert_eq!(SyntacticOrder::new(&sm).hasse(), vec![]);
+    }
+}
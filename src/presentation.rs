@@ -0,0 +1,247 @@
+use regex::word_to_string;
+use std::collections::VecDeque;
+use std::fmt;
+use syntactic_monoid::{ElemSet, ElemType, SyntacticMonoid};
+
+// A monoid presentation <generators | relations>. The relations u = v are
+// oriented as rewriting rules u -> v: v is the length-lexicographically least
+// word over the generators for its element, and u is a shortest word that is
+// not, so the rules are confluent. Letters outside the generating set are
+// kept in `eliminated` with the word over the generators they are equal to.
+#[derive(Debug, Clone)]
+pub struct Presentation {
+    pub generators: Vec<u8>,
+    pub relations: Vec<(Vec<u8>, Vec<u8>)>,
+    pub eliminated: Vec<(u8, Vec<u8>)>,
+}
+
+// the submonoid generated by the images of some letters
+fn generated(monoid: &SyntacticMonoid, letters: &[u8]) -> ElemSet {
+    let mut elements = ElemSet::new();
+    let mut queue: VecDeque<ElemType> = VecDeque::new();
+    elements.insert(0);
+    queue.push_back(0);
+    while let Some(e) = queue.pop_front() {
+        for a in letters {
+            let f = monoid.multiply(e, monoid.letter(*a).unwrap());
+            if elements.insert(f) {
+                queue.push_back(f);
+            }
+        }
+    }
+    elements
+}
+
+fn latex_word(word: &[u8]) -> String {
+    if word.is_empty() {
+        return "\\varepsilon".to_string();
+    }
+    word.iter()
+        .map(|a| match *a {
+            b'{' | b'}' | b'_' | b'#' | b'$' | b'%' | b'&' => format!("\\{}", *a as char),
+            b'\\' => "\\backslash{}".to_string(),
+            // a text-mode symbol, as the presentation is set in math mode
+            b'^' => "\\text{\\textasciicircum}".to_string(),
+            b'~' => "\\sim{}".to_string(),
+            b' ' => "\\ ".to_string(),
+            a if a.is_ascii_graphic() => (a as char).to_string(),
+            a => format!("\\backslash{{}}x{:02x}", a),
+        })
+        .collect()
+}
+
+impl Presentation {
+    pub fn new(monoid: &SyntacticMonoid) -> Self {
+        // one letter per non-trivial letter image, then drop the letters whose
+        // image the others already generate
+        let mut generators: Vec<u8> = Vec::new();
+        for a in monoid.alphabet().iter() {
+            let e = monoid.letter(a as u8).unwrap();
+            if e != 0 && generators.iter().all(|b| monoid.letter(*b).unwrap() != e) {
+                generators.push(a as u8);
+            }
+        }
+        for i in (0..generators.len()).rev() {
+            let mut others = generators.clone();
+            let a = others.remove(i);
+            if generated(monoid, &others).contains(&monoid.letter(a).unwrap()) {
+                generators = others;
+            }
+        }
+
+        // the rules of the enumeration over the generators are the relations
+        let over_generators = monoid.over_letters(&generators);
+        let relations = over_generators.rules().to_vec();
+        let eliminated = monoid
+            .alphabet()
+            .iter()
+            .map(|a| a as u8)
+            .filter(|a| !generators.contains(a))
+            .map(|a| {
+                let images = monoid.images(monoid.letter(a).unwrap());
+                let e = (0..over_generators.size())
+                    .find(|e| over_generators.images(*e) == images)
+                    .unwrap();
+                (a, over_generators.word(e).to_vec())
+            })
+            .collect();
+        Presentation {
+            generators,
+            relations,
+            eliminated,
+        }
+    }
+
+    // the normal form of a word over the generators
+    pub fn reduce(&self, word: &[u8]) -> Vec<u8> {
+        let mut word = word.to_vec();
+        'rewrite: loop {
+            for (u, v) in &self.relations {
+                if let Some(i) = word.windows(u.len()).position(|w| w == &u[..]) {
+                    word.splice(i..i + u.len(), v.iter().cloned());
+                    continue 'rewrite;
+                }
+            }
+            return word;
+        }
+    }
+
+    // rebuilds the monoid of normal forms from the presentation and checks that
+    // evaluating them in `monoid` is a bijective homomorphism
+    pub fn rebuilds(&self, monoid: &SyntacticMonoid) -> bool {
+        let mut normal_forms: Vec<Vec<u8>> = vec![Vec::new()];
+        let mut i = 0;
+        while i < normal_forms.len() {
+            if normal_forms.len() > monoid.size() {
+                return false;
+            }
+            for a in &self.generators {
+                let mut w = normal_forms[i].clone();
+                w.push(*a);
+                if self.reduce(&w) == w {
+                    normal_forms.push(w);
+                }
+            }
+            i += 1;
+        }
+        if normal_forms.len() != monoid.size() {
+            return false;
+        }
+        let values: Vec<ElemType> = match normal_forms.iter().map(|w| monoid.morphism(w)).collect()
+        {
+            Ok(values) => values,
+            Err(_) => return false,
+        };
+        let distinct: ElemSet = values.iter().cloned().collect();
+        if distinct.len() != values.len() {
+            return false;
+        }
+        for (u, eu) in normal_forms.iter().zip(&values) {
+            for (v, ev) in normal_forms.iter().zip(&values) {
+                let mut uv = u.clone();
+                uv.extend(v);
+                if monoid.morphism(&self.reduce(&uv)) != Ok(monoid.multiply(*eu, *ev)) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    pub fn to_latex(&self) -> String {
+        let generators: Vec<String> = self.generators.iter().map(|a| latex_word(&[*a])).collect();
+        let relations: Vec<String> = self
+            .relations
+            .iter()
+            .map(|(u, v)| format!("{} = {}", latex_word(u), latex_word(v)))
+            .collect();
+        let mut latex = format!(
+            "\\langle {} \\mid {} \\rangle",
+            generators.join(", "),
+            relations.join(",\\ ")
+        );
+        for &(a, ref w) in &self.eliminated {
+            latex += &format!(",\\quad {} = {}", latex_word(&[a]), latex_word(w));
+        }
+        latex
+    }
+}
+
+impl fmt::Display for Presentation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let generators: Vec<String> = self
+            .generators
+            .iter()
+            .map(|a| word_to_string(&[*a]))
+            .collect();
+        let relations: Vec<String> = self
+            .relations
+            .iter()
+            .map(|(u, v)| format!("{} = {}", word_to_string(u), word_to_string(v)))
+            .collect();
+        write!(f, "⟨{} | {}⟩", generators.join(", "), relations.join(", "))?;
+        if !self.eliminated.is_empty() {
+            let eliminated: Vec<String> = self
+                .eliminated
+                .iter()
+                .map(|&(a, ref w)| format!("{} = {}", word_to_string(&[a]), word_to_string(w)))
+                .collect();
+            write!(f, " where {}", eliminated.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntactic_monoid::tests::monoid;
+
+    #[test]
+    fn latex_words() {
+        assert_eq!(latex_word(b""), "\\varepsilon");
+        assert_eq!(latex_word(b"a1"), "a1");
+        assert_eq!(latex_word(b"^"), "\\text{\\textasciicircum}");
+        assert_eq!(latex_word(b"{_}"), "\\{\\_\\}");
+        assert_eq!(latex_word(b"\\~"), "\\backslash{}\\sim{}");
+    }
+
+    #[test]
+    fn relations_are_the_rules_of_the_monoid() {
+        let sm = monoid("(a|ba)*", "ab");
+        let p = Presentation::new(&sm);
+        assert_eq!(p.generators, b"ab".to_vec());
+        assert_eq!(p.relations, sm.rules().to_vec());
+        assert!(p.eliminated.is_empty());
+        assert!(p.rebuilds(&sm));
+        assert_eq!(
+            p.to_string(),
+            "⟨a, b | aa = a, aba = a, abb = bb, bab = b, bba = bb, bbb = bb⟩"
+        );
+    }
+
+    #[test]
+    fn eliminated_letters() {
+        // c acts as ab, and b as a
+        for &(pattern, letters, ref eliminated) in &[
+            ("(ab|c)*", "abc", (b'c', b"ab".to_vec())),
+            ("(a|b)*c", "abc", (b'b', b"a".to_vec())),
+        ] {
+            let sm = monoid(pattern, letters);
+            let p = Presentation::new(&sm);
+            assert_eq!(p.eliminated, vec![eliminated.clone()]);
+            assert!(p.rebuilds(&sm));
+            for (u, v) in &p.relations {
+                assert!(u.iter().chain(v).all(|b| p.generators.contains(b)));
+            }
+        }
+        let sm = monoid("a*", "ab");
+        let p = Presentation::new(&sm);
+        assert_eq!(p.generators, b"b".to_vec());
+        assert_eq!(p.eliminated, vec![(b'a', Vec::new())]);
+        assert_eq!(
+            p.to_latex(),
+            "\\langle b \\mid bb = b \\rangle,\\quad a = \\varepsilon"
+        );
+    }
+}
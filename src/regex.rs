@@ -1,48 +1,276 @@
+use bit_set::BitSet;
+use std::fmt;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RegularExpression {
     Empty,
     Epsilon,
-    Char(u8), // only ASCII
+    Char(u8),            // only ASCII
+    Class(BitSet, bool), // members, and whether written as `.` or `[^...]`
     Union(Box<RegularExpression>, Box<RegularExpression>),
     Concat(Box<RegularExpression>, Box<RegularExpression>),
     Kleene(Box<RegularExpression>),
+    Repeat(Box<RegularExpression>, usize, Option<usize>), // e{min,max}
+    Intersection(Box<RegularExpression>, Box<RegularExpression>),
+    Complement(Box<RegularExpression>),
 }
 
 /*
  *
- * <union> ::= <concat>
- *                | <concat> "|" <union>
- * <concat> ::= <kleene>
- *          | <kleene> <concat>
+ * <union> ::= <intersection>
+ *                | <intersection> "|" <union>
+ * <intersection> ::= <concat>
+ *                | <concat> "&" <intersection>
+ * <concat> ::= <complement>
+ *          | <complement> <concat>
+ * <complement> ::= <kleene>
+ *          |   "!" <complement>
  * <kleene> ::= <factor>
- *          |   <factor> "*"
- * <factor> ::= <alphabet> | "(" <expression> ")"
+ *          |   <kleene> "*" | <kleene> "+" | <kleene> "?"
+ *          |   <kleene> "{" <num> "}" | <kleene> "{" <num> ",}"
+ *          |   <kleene> "{" <num> "," <num> "}"
+ * <factor> ::= <alphabet> | "(" <expression> ")" | "ε" | "∅" | "@"
+ *          |   "." | "[" <class> "]" | "[^" <class> "]"
+ * <class> ::= <item> | <item> <class>
+ * <item> ::= <alphabet> | <alphabet> "-" <alphabet>
  * <alphabet> ::= 0 | .. | 255 ( ASCII code )
+ *          |   "\" <metachar> | "\n" | "\t" | "\x" <hex> <hex>
  */
 
+// characters that must be escaped to be read as a literal
+const METACHARS: &str = "()|&!@*+?{[.\\";
+const CLASS_METACHARS: &str = "]^-\\";
+
+// repetitions are expanded into copies of their operand, so counts, and their
+// products across nested repetitions, are bounded
+pub const MAX_REPEAT: usize = 1000;
+
+fn escape_char_in(a: u8, metachars: &str) -> String {
+    match a {
+        b'\n' => "\\n".to_string(),
+        b'\t' => "\\t".to_string(),
+        _ if metachars.contains(a as char) => format!("\\{}", a as char),
+        0x20..=0x7e => (a as char).to_string(),
+        _ => format!("\\x{:02x}", a),
+    }
+}
+
+pub(crate) fn escape_char(a: u8) -> String {
+    escape_char_in(a, METACHARS)
+}
+
+pub fn full_alphabet() -> BitSet {
+    let mut alphabet = BitSet::with_capacity(256);
+    for c in 0..256 {
+        alphabet.insert(c);
+    }
+    alphabet
+}
+
+// renders a word over bytes, writing the empty word as ε
+pub fn word_to_string(word: &[u8]) -> String {
+    if word.is_empty() {
+        return "ε".to_string();
+    }
+    word.iter().map(|a| escape_char_in(*a, "")).collect()
+}
+
+pub fn class_to_string(class: &BitSet) -> String {
+    // the shorter of the two forms
+    negated_class_to_string(class, class.len() > 128)
+}
+
+fn negated_class_to_string(class: &BitSet, negated: bool) -> String {
+    // an empty class has no bracket syntax, so it is written as the empty set
+    match (class.len(), negated) {
+        (0, false) => return "∅".to_string(),
+        (256, true) => return ".".to_string(),
+        _ => {}
+    }
+    let (negated, members) = if negated {
+        ("^", (0..256).filter(|c| !class.contains(*c)).collect())
+    } else {
+        ("", class.iter().collect::<Vec<usize>>())
+    };
+    let mut s = String::new();
+    let mut i = 0;
+    while i < members.len() {
+        // collapse runs of three or more consecutive bytes into a range
+        let mut j = i;
+        while j + 1 < members.len() && members[j + 1] == members[j] + 1 {
+            j += 1;
+        }
+        s += &escape_char_in(members[i] as u8, CLASS_METACHARS);
+        if j >= i + 2 {
+            s += "-";
+            s += &escape_char_in(members[j] as u8, CLASS_METACHARS);
+            i = j + 1;
+        } else {
+            i += 1;
+        }
+    }
+    format!("[{}{}]", negated, s)
+}
+
 impl RegularExpression {
-    pub fn to_string(&self) -> String {
+    // the letters occurring in the expression
+    pub fn alphabet(&self) -> BitSet {
+        let mut alphabet = BitSet::with_capacity(256);
+        self.collect_letters(&mut alphabet);
+        alphabet
+    }
+
+    // how many copies of its innermost operand the nested repetitions expand
+    // into, so that a{m}{n} counts as m * n
+    fn copies(&self) -> usize {
         match *self {
-            RegularExpression::Empty => "∅".to_string(),
-            RegularExpression::Epsilon => "ε".to_string(),
-            RegularExpression::Char(a) => (a as char).to_string(),
-            RegularExpression::Concat(ref e1, ref e2) => e1.to_string() + &e2.to_string(),
-            RegularExpression::Union(ref e1, ref e2) => {
-                "(".to_string() + &e1.to_string() + "|" + &e2.to_string() + ")"
-            }
-            RegularExpression::Kleene(ref e) => match **e {
-                RegularExpression::Concat(_, _) => "(".to_string() + &e.to_string() + ")*",
-                _ => e.to_string() + "*",
+            RegularExpression::Empty
+            | RegularExpression::Epsilon
+            | RegularExpression::Char(_)
+            | RegularExpression::Class(_, _) => 1,
+            RegularExpression::Union(ref e1, ref e2)
+            | RegularExpression::Concat(ref e1, ref e2)
+            | RegularExpression::Intersection(ref e1, ref e2) => e1.copies().max(e2.copies()),
+            RegularExpression::Kleene(ref e) | RegularExpression::Complement(ref e) => e.copies(),
+            RegularExpression::Repeat(ref e, min, max) => {
+                max.unwrap_or(min).max(1).saturating_mul(e.copies())
+            }
+        }
+    }
+
+    fn collect_letters(&self, alphabet: &mut BitSet) {
+        match *self {
+            RegularExpression::Empty | RegularExpression::Epsilon => {}
+            RegularExpression::Char(a) => {
+                alphabet.insert(a as usize);
+            }
+            // `.` and `[^...]` do not name letters
+            RegularExpression::Class(_, true) => {}
+            RegularExpression::Class(ref class, false) => alphabet.union_with(class),
+            RegularExpression::Union(ref e1, ref e2)
+            | RegularExpression::Concat(ref e1, ref e2)
+            | RegularExpression::Intersection(ref e1, ref e2) => {
+                e1.collect_letters(alphabet);
+                e2.collect_letters(alphabet);
+            }
+            RegularExpression::Kleene(ref e)
+            | RegularExpression::Repeat(ref e, _, _)
+            | RegularExpression::Complement(ref e) => e.collect_letters(alphabet),
+        }
+    }
+}
+
+impl fmt::Display for RegularExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RegularExpression::Empty => write!(f, "∅"),
+            RegularExpression::Epsilon => write!(f, "ε"),
+            RegularExpression::Char(a) => write!(f, "{}", escape_char(a)),
+            RegularExpression::Class(ref class, negated) => {
+                write!(f, "{}", negated_class_to_string(class, negated))
+            }
+            RegularExpression::Concat(ref e1, ref e2) => match **e2 {
+                // concatenation is read left-associatively
+                RegularExpression::Concat(_, _) => write!(f, "{}({})", e1, e2),
+                _ => write!(f, "{}{}", e1, e2),
+            },
+            RegularExpression::Union(ref e1, ref e2) => write!(f, "({}|{})", e1, e2),
+            RegularExpression::Intersection(ref e1, ref e2) => write!(f, "({}&{})", e1, e2),
+            RegularExpression::Complement(ref e) => match **e {
+                RegularExpression::Concat(_, _) => write!(f, "!({})", e),
+                _ => write!(f, "!{}", e),
             },
+            RegularExpression::Kleene(ref e) => write_postfix(f, e, "*"),
+            RegularExpression::Repeat(ref e, min, max) => {
+                let op = match (min, max) {
+                    (1, None) => "+".to_string(),
+                    (0, Some(1)) => "?".to_string(),
+                    (min, None) => format!("{{{},}}", min),
+                    (min, Some(max)) if min == max => format!("{{{}}}", min),
+                    (min, Some(max)) => format!("{{{},{}}}", min, max),
+                };
+                write_postfix(f, e, &op)
+            }
         }
     }
 }
 
+fn write_postfix(f: &mut fmt::Formatter, e: &RegularExpression, op: &str) -> fmt::Result {
+    match *e {
+        RegularExpression::Concat(_, _)
+        | RegularExpression::Kleene(_)
+        | RegularExpression::Repeat(_, _, _)
+        | RegularExpression::Complement(_) => write!(f, "({}){}", e, op),
+        _ => write!(f, "{}{}", e, op),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnexpectedEnd,
+    UnterminatedGroup,
+    UnmatchedParen,
+    EmptyGroup,
+    DanglingUnion,
+    DanglingIntersection,
+    NothingToRepeat,
+    InvalidRepeat,
+    RepeatTooLarge,
+    NotStarFree,
+    InvalidEscape,
+    UnterminatedClass,
+    EmptyClass,
+    InvalidRange,
+    NonAsciiInClass,
+    TrailingInput,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub pos: usize, // byte offset into the pattern
+    pub expected: Vec<&'static str>,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self.kind {
+            ParseErrorKind::UnexpectedEnd => "unexpected end of pattern",
+            ParseErrorKind::UnterminatedGroup => "unterminated (",
+            ParseErrorKind::UnmatchedParen => "unmatched )",
+            ParseErrorKind::EmptyGroup => "empty group",
+            ParseErrorKind::DanglingUnion => "missing operand of |",
+            ParseErrorKind::DanglingIntersection => "missing operand of &",
+            ParseErrorKind::NothingToRepeat => "nothing to repeat",
+            ParseErrorKind::InvalidRepeat => "invalid repetition count",
+            ParseErrorKind::RepeatTooLarge => "repetition count too large",
+            ParseErrorKind::NotStarFree => "operator not allowed in a star-free expression",
+            ParseErrorKind::InvalidEscape => "invalid escape sequence",
+            ParseErrorKind::UnterminatedClass => "unterminated [",
+            ParseErrorKind::EmptyClass => "empty character class",
+            ParseErrorKind::InvalidRange => "invalid range in character class",
+            ParseErrorKind::NonAsciiInClass => "non-ASCII character in character class",
+            ParseErrorKind::TrailingInput => "trailing input",
+        };
+        write!(f, "{} at {}", msg, self.pos)?;
+        if !self.expected.is_empty() {
+            write!(f, ", expected {}", self.expected.join(" or "))?;
+        }
+        Ok(())
+    }
+}
+
 pub struct Parser {
     cur: usize,
     chars: Vec<char>,
 }
 
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Parser {
     pub fn new() -> Self {
         Parser {
@@ -50,84 +278,657 @@ impl Parser {
             chars: Vec::new(),
         }
     }
-    pub fn parse(&mut self, text: &String) -> Option<RegularExpression> {
+    pub fn parse(&mut self, text: &str) -> Result<RegularExpression, ParseError> {
+        self.reset(text);
+        let exp = self.read_union()?;
+        match self.peek() {
+            None => Ok(exp),
+            Some(')') => Err(self.error(
+                ParseErrorKind::UnmatchedParen,
+                vec!["'|'", "end of pattern"],
+            )),
+            Some(_) => Err(self.error(ParseErrorKind::TrailingInput, vec!["end of pattern"])),
+        }
+    }
+
+    // reads an alphabet written like the inside of a character class, e.g. "a-z0-9"
+    pub fn parse_alphabet(&mut self, text: &str) -> Result<BitSet, ParseError> {
+        self.reset(text);
+        let mut alphabet = BitSet::with_capacity(256);
+        while self.peek().is_some() {
+            let (from, to) = self.read_class_item()?;
+            for c in from..=to {
+                alphabet.insert(c as usize);
+            }
+        }
+        Ok(alphabet)
+    }
+
+    pub(crate) fn reset(&mut self, text: &str) {
         self.chars = text.chars().collect();
-        self.chars.push('\0');
         self.cur = 0;
-        self.read_union()
     }
 
-    fn read_union(&mut self) -> Option<RegularExpression> {
-        let mut expleft: Option<RegularExpression> = self.read_concat();
-        while self.chars[self.cur] == '|' {
-            self.cur = self.cur + 1;
-            let expright = match self.read_concat() {
-                Some(term) => term,
-                None => {
-                    return None;
-                }
-            };
-            let el = expleft.unwrap().clone();
-            expleft = Some(RegularExpression::Union(Box::new(el), Box::new(expright)));
+    pub(crate) fn peek(&self) -> Option<char> {
+        self.chars.get(self.cur).cloned()
+    }
+
+    // moves past the current character
+    pub(crate) fn bump(&mut self) {
+        self.cur += 1;
+    }
+
+    // the index of the current character, for `error_at`
+    pub(crate) fn position(&self) -> usize {
+        self.cur
+    }
+
+    // the byte offset of the character at index `cur`
+    pub(crate) fn offset(&self, cur: usize) -> usize {
+        self.chars[..cur].iter().map(|c| c.len_utf8()).sum()
+    }
+
+    pub(crate) fn error(&self, kind: ParseErrorKind, expected: Vec<&'static str>) -> ParseError {
+        self.error_at(self.cur, kind, expected)
+    }
+
+    pub(crate) fn error_at(
+        &self,
+        cur: usize,
+        kind: ParseErrorKind,
+        expected: Vec<&'static str>,
+    ) -> ParseError {
+        ParseError {
+            kind,
+            pos: self.offset(cur),
+            expected,
+        }
+    }
+
+    fn read_union(&mut self) -> Result<RegularExpression, ParseError> {
+        if self.peek() == Some('|') {
+            return Err(self.error(ParseErrorKind::DanglingUnion, vec!["character", "'('"]));
+        }
+        let mut expleft = self.read_intersection()?;
+        while self.peek() == Some('|') {
+            self.read_operator(ParseErrorKind::DanglingUnion)?;
+            let expright = self.read_intersection()?;
+            expleft = RegularExpression::Union(Box::new(expleft), Box::new(expright));
+        }
+        Ok(expleft)
+    }
+
+    fn read_intersection(&mut self) -> Result<RegularExpression, ParseError> {
+        if self.peek() == Some('&') {
+            return Err(self.error(
+                ParseErrorKind::DanglingIntersection,
+                vec!["character", "'('"],
+            ));
+        }
+        let mut expleft = self.read_concat()?;
+        while self.peek() == Some('&') {
+            self.read_operator(ParseErrorKind::DanglingIntersection)?;
+            let expright = self.read_concat()?;
+            expleft = RegularExpression::Intersection(Box::new(expleft), Box::new(expright));
+        }
+        Ok(expleft)
+    }
+
+    // skips a binary operator, failing if its right operand is missing
+    fn read_operator(&mut self, dangling: ParseErrorKind) -> Result<(), ParseError> {
+        let op = self.cur;
+        self.cur += 1;
+        match self.peek() {
+            Some('|') | Some('&') | Some(')') | None => {
+                Err(self.error_at(op, dangling, vec!["character", "'('"]))
+            }
+            _ => Ok(()),
         }
-        expleft
     }
 
-    fn read_concat(&mut self) -> Option<RegularExpression> {
-        let mut expleft: Option<RegularExpression> = self.read_kleene();
-        while self.chars[self.cur] != '\0' && self.chars[self.cur] != ')'
-            && self.chars[self.cur] != '|'
+    fn read_concat(&mut self) -> Result<RegularExpression, ParseError> {
+        let mut expleft = self.read_complement()?;
+        while self.peek().is_some()
+            && self.peek() != Some(')')
+            && self.peek() != Some('|')
+            && self.peek() != Some('&')
         {
-            let expright = match self.read_kleene() {
-                Some(term) => term,
-                None => {
-                    return None;
+            let expright = self.read_complement()?;
+            expleft = RegularExpression::Concat(Box::new(expleft), Box::new(expright));
+        }
+        Ok(expleft)
+    }
+    fn read_complement(&mut self) -> Result<RegularExpression, ParseError> {
+        if self.peek() == Some('!') {
+            self.cur += 1;
+            let exp = self.read_complement()?;
+            Ok(RegularExpression::Complement(Box::new(exp)))
+        } else {
+            self.read_kleene()
+        }
+    }
+    fn read_kleene(&mut self) -> Result<RegularExpression, ParseError> {
+        let mut exp = self.read_factor()?;
+        loop {
+            exp = match self.peek() {
+                Some('*') => RegularExpression::Kleene(Box::new(exp)),
+                Some('+') => RegularExpression::Repeat(Box::new(exp), 1, None),
+                Some('?') => RegularExpression::Repeat(Box::new(exp), 0, Some(1)),
+                Some('{') => {
+                    let (min, max, count) = self.read_bounds()?;
+                    let exp = RegularExpression::Repeat(Box::new(exp), min, max);
+                    if exp.copies() > MAX_REPEAT {
+                        return Err(self.error_at(
+                            count,
+                            ParseErrorKind::RepeatTooLarge,
+                            vec!["number up to 1000 in total"],
+                        ));
+                    }
+                    exp
                 }
+                _ => return Ok(exp),
             };
-
-            let el = expleft.unwrap().clone();
-            expleft = Some(RegularExpression::Concat(Box::new(el), Box::new(expright)));
+            self.cur += 1;
         }
-        expleft
     }
-    fn read_kleene(&mut self) -> Option<RegularExpression> {
-        match self.read_factor() {
-            Some(exp) => {
-                let ch = self.chars[self.cur];
-                if ch == '*' {
-                    self.cur = self.cur + 1;
-                    Some(RegularExpression::Kleene(Box::new(exp)))
-                } else {
-                    Some(exp)
-                }
+    // reads "{m}", "{m,}" or "{m,n}" and leaves the cursor on the closing '}';
+    // also returns the position of the count that bounds the number of copies
+    fn read_bounds(&mut self) -> Result<(usize, Option<usize>, usize), ParseError> {
+        let open = self.cur;
+        self.cur += 1;
+        let mut count = self.cur;
+        let min = self.read_count(vec!["number"])?;
+        let max = if self.peek() == Some(',') {
+            self.cur += 1;
+            if self.peek() == Some('}') {
+                None
+            } else {
+                count = self.cur;
+                Some(self.read_count(vec!["number", "'}'"])?)
+            }
+        } else {
+            Some(min)
+        };
+        if self.peek() != Some('}') {
+            return Err(self.error(ParseErrorKind::InvalidRepeat, vec!["','", "'}'"]));
+        }
+        if let Some(max) = max {
+            if max < min {
+                return Err(self.error_at(open, ParseErrorKind::InvalidRepeat, vec![]));
             }
-            None => None,
+        }
+        Ok((min, max, count))
+    }
+    // reads a repetition count of at most MAX_REPEAT
+    fn read_count(&mut self, expected: Vec<&'static str>) -> Result<usize, ParseError> {
+        let start = self.cur;
+        match self.read_number() {
+            Some(n) if n <= MAX_REPEAT => Ok(n),
+            None if self.cur == start => Err(self.error(ParseErrorKind::InvalidRepeat, expected)),
+            // too large, or too large for usize
+            _ => Err(self.error_at(
+                start,
+                ParseErrorKind::RepeatTooLarge,
+                vec!["number up to 1000"],
+            )),
         }
     }
-    fn read_factor(&mut self) -> Option<RegularExpression> {
-        let ch = self.chars[self.cur];
+    pub(crate) fn read_number(&mut self) -> Option<usize> {
+        let mut n: Option<usize> = None;
+        while let Some(d) = self.peek().and_then(|c| c.to_digit(10)) {
+            n = Some(n.unwrap_or(0).checked_mul(10)?.checked_add(d as usize)?);
+            self.cur += 1;
+        }
+        n
+    }
+    pub(crate) fn read_factor(&mut self) -> Result<RegularExpression, ParseError> {
+        let ch = match self.peek() {
+            Some(ch) => ch,
+            None => {
+                return Err(self.error(ParseErrorKind::UnexpectedEnd, vec!["character", "'('"]));
+            }
+        };
         match ch {
             '(' => {
-                self.cur = self.cur + 1;
-                let exp = match &self.read_union() {
-                    &Some(ref exp) => exp.clone(),
-                    &None => {
-                        return None;
+                let open = self.cur;
+                self.cur += 1;
+                match self.peek() {
+                    Some(')') => {
+                        return Err(
+                            self.error(ParseErrorKind::EmptyGroup, vec!["character", "'('"])
+                        );
                     }
-                };
-                if self.chars[self.cur] != ')' {
-                    println!("unterminated (");
-                    return None;
+                    None => {
+                        return Err(self.error_at(
+                            open,
+                            ParseErrorKind::UnterminatedGroup,
+                            vec!["')'"],
+                        ));
+                    }
+                    _ => {}
+                }
+                let exp = self.read_union()?;
+                if self.peek() != Some(')') {
+                    return Err(self.error_at(
+                        open,
+                        ParseErrorKind::UnterminatedGroup,
+                        vec!["')'"],
+                    ));
                 }
-                self.cur = self.cur + 1;
-                Some(exp)
+                self.cur += 1;
+                Ok(exp)
+            }
+            ')' => Err(self.error(ParseErrorKind::UnmatchedParen, vec!["character", "'('"])),
+            '|' => Err(self.error(ParseErrorKind::DanglingUnion, vec!["character", "'('"])),
+            '&' => Err(self.error(
+                ParseErrorKind::DanglingIntersection,
+                vec!["character", "'('"],
+            )),
+            '*' | '+' | '?' | '{' => {
+                Err(self.error(ParseErrorKind::NothingToRepeat, vec!["character", "'('"]))
+            }
+            '\\' => Ok(RegularExpression::Char(self.read_escape()?)),
+            '[' => self.read_class(),
+            '.' => {
+                self.cur += 1;
+                Ok(RegularExpression::Class(full_alphabet(), true))
+            }
+            'ε' => {
+                self.cur += 1;
+                Ok(RegularExpression::Epsilon)
+            }
+            '∅' | '@' => {
+                self.cur += 1;
+                Ok(RegularExpression::Empty)
             }
-            ')' => None,
-            '\0' => None,
             ch => {
-                self.cur = self.cur + 1;
-                Some(RegularExpression::Char(ch as u8))
+                self.cur += 1;
+                let mut buf = [0; 4];
+                let bytes = ch.encode_utf8(&mut buf).as_bytes();
+                // non-ASCII characters stand for their UTF-8 byte sequence
+                let mut exp = RegularExpression::Char(bytes[0]);
+                for b in &bytes[1..] {
+                    exp = RegularExpression::Concat(
+                        Box::new(exp),
+                        Box::new(RegularExpression::Char(*b)),
+                    );
+                }
+                Ok(exp)
+            }
+        }
+    }
+    fn read_class(&mut self) -> Result<RegularExpression, ParseError> {
+        let open = self.cur;
+        self.cur += 1;
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.cur += 1;
+        }
+        let mut class = BitSet::with_capacity(256);
+        loop {
+            match self.peek() {
+                Some(']') => break,
+                Some(_) => {
+                    let (from, to) = self.read_class_item()?;
+                    for c in from..=to {
+                        class.insert(c as usize);
+                    }
+                }
+                None => {
+                    return Err(self.error_at(
+                        open,
+                        ParseErrorKind::UnterminatedClass,
+                        vec!["']'"],
+                    ));
+                }
             }
         }
+        if self.cur == open + 1 + negated as usize {
+            return Err(self.error(ParseErrorKind::EmptyClass, vec!["character"]));
+        }
+        self.cur += 1;
+        if negated {
+            let mut complement = full_alphabet();
+            complement.difference_with(&class);
+            class = complement;
+        }
+        Ok(RegularExpression::Class(class, negated))
+    }
+    // reads a single character or a range "a-z"
+    fn read_class_item(&mut self) -> Result<(u8, u8), ParseError> {
+        let item = self.cur;
+        let from = self.read_class_char()?;
+        let mut to = from;
+        // a '-' right before ']' or the end is read as a literal
+        if self.peek() == Some('-')
+            && self.chars.get(self.cur + 1) != Some(&']')
+            && self.cur + 1 < self.chars.len()
+        {
+            self.cur += 1;
+            to = self.read_class_char()?;
+            if to < from {
+                return Err(self.error_at(item, ParseErrorKind::InvalidRange, vec![]));
+            }
+        }
+        Ok((from, to))
+    }
+    fn read_class_char(&mut self) -> Result<u8, ParseError> {
+        match self.peek() {
+            Some('\\') => self.read_escape(),
+            Some(ch) if (ch as u32) < 0x80 => {
+                self.cur += 1;
+                Ok(ch as u8)
+            }
+            _ => Err(self.error(ParseErrorKind::NonAsciiInClass, vec!["ASCII character"])),
+        }
+    }
+    fn read_escape(&mut self) -> Result<u8, ParseError> {
+        let backslash = self.cur;
+        self.cur += 1;
+        let ch = match self.peek() {
+            Some(ch) => ch,
+            None => {
+                return Err(self.error_at(
+                    backslash,
+                    ParseErrorKind::InvalidEscape,
+                    vec!["escaped character"],
+                ));
+            }
+        };
+        self.cur += 1;
+        match ch {
+            'n' => Ok(b'\n'),
+            't' => Ok(b'\t'),
+            'x' => {
+                let mut code = 0u8;
+                for _ in 0..2 {
+                    match self.peek().and_then(|c| c.to_digit(16)) {
+                        Some(d) => code = code * 16 + d as u8,
+                        None => {
+                            return Err(self.error_at(
+                                backslash,
+                                ParseErrorKind::InvalidEscape,
+                                vec!["two hex digits"],
+                            ));
+                        }
+                    }
+                    self.cur += 1;
+                }
+                Ok(code)
+            }
+            ch if ch.is_ascii_punctuation() => Ok(ch as u8),
+            _ => Err(self.error_at(
+                backslash,
+                ParseErrorKind::InvalidEscape,
+                vec!["metacharacter", "'n'", "'t'", "'x'"],
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(pattern: &str) -> (ParseErrorKind, usize, Vec<&'static str>) {
+        let e = Parser::new().parse(pattern).unwrap_err();
+        (e.kind, e.pos, e.expected)
+    }
+
+    #[test]
+    fn unexpected_end() {
+        assert_eq!(
+            error(""),
+            (ParseErrorKind::UnexpectedEnd, 0, vec!["character", "'('"])
+        );
+        assert_eq!(
+            error("a!"),
+            (ParseErrorKind::UnexpectedEnd, 2, vec!["character", "'('"])
+        );
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(
+            error("a(b"),
+            (ParseErrorKind::UnterminatedGroup, 1, vec!["')'"])
+        );
+        assert_eq!(
+            error("(("),
+            (ParseErrorKind::UnterminatedGroup, 1, vec!["')'"])
+        );
+        assert_eq!(
+            error("a)"),
+            (
+                ParseErrorKind::UnmatchedParen,
+                1,
+                vec!["'|'", "end of pattern"]
+            )
+        );
+        assert_eq!(
+            error(")"),
+            (ParseErrorKind::UnmatchedParen, 0, vec!["character", "'('"])
+        );
+        assert_eq!(
+            error("a()"),
+            (ParseErrorKind::EmptyGroup, 2, vec!["character", "'('"])
+        );
+    }
+
+    #[test]
+    fn dangling_operators() {
+        assert_eq!(
+            error("|a"),
+            (ParseErrorKind::DanglingUnion, 0, vec!["character", "'('"])
+        );
+        assert_eq!(
+            error("a|"),
+            (ParseErrorKind::DanglingUnion, 1, vec!["character", "'('"])
+        );
+        assert_eq!(
+            error("(a||b)"),
+            (ParseErrorKind::DanglingUnion, 2, vec!["character", "'('"])
+        );
+        assert_eq!(
+            error("&a"),
+            (
+                ParseErrorKind::DanglingIntersection,
+                0,
+                vec!["character", "'('"]
+            )
+        );
+        assert_eq!(
+            error("a&)"),
+            (
+                ParseErrorKind::DanglingIntersection,
+                1,
+                vec!["character", "'('"]
+            )
+        );
+    }
+
+    #[test]
+    fn repetitions() {
+        assert_eq!(
+            error("*a"),
+            (ParseErrorKind::NothingToRepeat, 0, vec!["character", "'('"])
+        );
+        assert_eq!(
+            error("a|{2}"),
+            (ParseErrorKind::NothingToRepeat, 2, vec!["character", "'('"])
+        );
+        assert_eq!(
+            error("a{x}"),
+            (ParseErrorKind::InvalidRepeat, 2, vec!["number"])
+        );
+        assert_eq!(
+            error("a{2,x}"),
+            (ParseErrorKind::InvalidRepeat, 4, vec!["number", "'}'"])
+        );
+        assert_eq!(
+            error("a{2"),
+            (ParseErrorKind::InvalidRepeat, 3, vec!["','", "'}'"])
+        );
+        assert_eq!(error("a{3,2}"), (ParseErrorKind::InvalidRepeat, 1, vec![]));
+        assert_eq!(
+            error("a{1001}"),
+            (ParseErrorKind::RepeatTooLarge, 2, vec!["number up to 1000"])
+        );
+        assert_eq!(
+            error("a{2,100000}"),
+            (ParseErrorKind::RepeatTooLarge, 4, vec!["number up to 1000"])
+        );
+        assert_eq!(
+            error("a{99999999999999999999999}"),
+            (ParseErrorKind::RepeatTooLarge, 2, vec!["number up to 1000"])
+        );
+    }
+
+    #[test]
+    fn repetition_bounds() {
+        let a = || Box::new(RegularExpression::Char(b'a'));
+        let parse = |text: &str| Parser::new().parse(text).unwrap();
+        assert_eq!(parse("a{3}"), RegularExpression::Repeat(a(), 3, Some(3)));
+        assert_eq!(parse("a{3,}"), RegularExpression::Repeat(a(), 3, None));
+        assert_eq!(parse("a{2,5}"), RegularExpression::Repeat(a(), 2, Some(5)));
+        assert_eq!(parse("a{0,0}"), RegularExpression::Repeat(a(), 0, Some(0)));
+        assert_eq!(
+            parse("a{1000}"),
+            RegularExpression::Repeat(a(), MAX_REPEAT, Some(MAX_REPEAT))
+        );
+        assert_eq!(error("a{5,2}"), (ParseErrorKind::InvalidRepeat, 1, vec![]));
+    }
+
+    #[test]
+    fn nested_repetitions() {
+        let too_large = |at| {
+            (
+                ParseErrorKind::RepeatTooLarge,
+                at,
+                vec!["number up to 1000 in total"],
+            )
+        };
+        assert!(Parser::new().parse("(a{10}){100}").is_ok());
+        assert!(Parser::new().parse("((a{2}b){5,}){100}").is_ok());
+        assert_eq!(error("(a{1000}){1000}"), too_large(10));
+        assert_eq!(error("(a{10}){2,101}"), too_large(10));
+        assert_eq!(error("((a{10})*b|c){101,}"), too_large(14));
+        assert_eq!(error("!(a{100}){11}"), too_large(10));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            error("a\\"),
+            (ParseErrorKind::InvalidEscape, 1, vec!["escaped character"])
+        );
+        assert_eq!(
+            error("\\x4g"),
+            (ParseErrorKind::InvalidEscape, 0, vec!["two hex digits"])
+        );
+        assert_eq!(
+            error("a\\q"),
+            (
+                ParseErrorKind::InvalidEscape,
+                1,
+                vec!["metacharacter", "'n'", "'t'", "'x'"]
+            )
+        );
+    }
+
+    #[test]
+    fn classes() {
+        assert_eq!(
+            error("a[bc"),
+            (ParseErrorKind::UnterminatedClass, 1, vec!["']'"])
+        );
+        assert_eq!(
+            error("[]"),
+            (ParseErrorKind::EmptyClass, 1, vec!["character"])
+        );
+        assert_eq!(
+            error("[^]"),
+            (ParseErrorKind::EmptyClass, 2, vec!["character"])
+        );
+        assert_eq!(error("[az-a]"), (ParseErrorKind::InvalidRange, 2, vec![]));
+        assert_eq!(
+            error("[aé]"),
+            (ParseErrorKind::NonAsciiInClass, 2, vec!["ASCII character"])
+        );
+    }
+
+    #[test]
+    fn position_is_a_byte_offset() {
+        assert_eq!(
+            error("εé)"),
+            (
+                ParseErrorKind::UnmatchedParen,
+                4,
+                vec!["'|'", "end of pattern"]
+            )
+        );
+    }
+
+    fn round_trip(re: &RegularExpression) {
+        let text = re.to_string();
+        assert_eq!(Parser::new().parse(&text).as_ref(), Ok(re), "{}", text);
+    }
+
+    #[test]
+    fn letters_round_trip() {
+        for a in 0..256 {
+            round_trip(&RegularExpression::Char(a as u8));
+        }
+    }
+
+    fn class(members: &[u8]) -> BitSet {
+        members.iter().map(|a| *a as usize).collect()
+    }
+
+    #[test]
+    fn classes_round_trip() {
+        let mut negated = full_alphabet();
+        negated.difference_with(&class(b"ab"));
+        let mut all_but_one = full_alphabet();
+        all_but_one.remove(0);
+        let classes = vec![
+            full_alphabet(),
+            all_but_one,
+            negated,
+            class(b"a"),
+            class(b"ab"),
+            class(b"abcdxyz"),
+            class(b"]^-\\"),
+            class(b"\n\t\x00\x7f\xff"),
+            class(b"()|&!@*+?{[."),
+            (0x80..0x100).collect(),
+        ];
+        for c in classes {
+            round_trip(&RegularExpression::Class(c.clone(), false));
+            round_trip(&RegularExpression::Class(c, true));
+        }
+        round_trip(&RegularExpression::Class(BitSet::new(), true));
+        // the empty class reads back as the empty set
+        let text = RegularExpression::Class(BitSet::new(), false).to_string();
+        assert_eq!(Parser::new().parse(&text), Ok(RegularExpression::Empty));
+    }
+
+    #[test]
+    fn inferred_alphabet() {
+        let alphabet = |pattern: &str| Parser::new().parse(pattern).unwrap().alphabet();
+        assert_eq!(alphabet("(a|ba)*"), class(b"ab"));
+        assert_eq!(alphabet("[a-c]d"), class(b"abcd"));
+        assert_eq!(alphabet("!(.*aa.*)"), class(b"a"));
+        assert_eq!(alphabet("[^a]b"), class(b"b"));
+        assert_eq!(alphabet(".*"), BitSet::new());
+        // a class is left out for being written negated, not for its size
+        assert_eq!(alphabet("[\\x00-\\x80]"), (0..0x81).collect());
+        assert_eq!(alphabet("[^\\x00-\\x80]"), BitSet::new());
+    }
+
+    #[test]
+    fn alphabet_errors() {
+        let e = Parser::new().parse_alphabet("a-c\\").unwrap_err();
+        assert_eq!((e.kind, e.pos), (ParseErrorKind::InvalidEscape, 3));
     }
 }
@@ -0,0 +1,384 @@
+use bit_set::BitSet;
+use dfa::DFA;
+use nfa::NFA;
+use regex::{class_to_string, escape_char};
+use regex::{ParseError, ParseErrorKind, Parser, RegularExpression};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StarFreeExpr {
+    Empty,
+    Letter(u8),
+    Class(BitSet),
+    Concat(Vec<StarFreeExpr>), // the empty concatenation is ε
+    Union(Vec<StarFreeExpr>),
+    Complement(Box<StarFreeExpr>),
+}
+
+/*
+ *
+ * <union> ::= <concat>
+ *                | <concat> "|" <union>
+ * <concat> ::= ( empty )
+ *          | <complement> <concat>
+ * <complement> ::= <atom>
+ *          |   "!" <complement>
+ * <atom> ::= "@" | "(" <union> ")" | <alphabet> | "." | "[" <class> "]"
+ */
+
+impl StarFreeExpr {
+    pub fn epsilon() -> Self {
+        StarFreeExpr::Concat(Vec::new())
+    }
+
+    // A*, written as the complement of the empty set
+    pub fn universe() -> Self {
+        StarFreeExpr::Complement(Box::new(StarFreeExpr::Empty))
+    }
+
+    pub fn class(class: BitSet) -> Self {
+        match class.len() {
+            0 => StarFreeExpr::Empty,
+            1 => StarFreeExpr::Letter(class.iter().next().unwrap() as u8),
+            _ => StarFreeExpr::Class(class),
+        }
+    }
+
+    pub fn concat(parts: Vec<StarFreeExpr>) -> Self {
+        let mut flat = Vec::new();
+        for part in parts {
+            match part {
+                StarFreeExpr::Concat(inner) => flat.extend(inner),
+                part => flat.push(part),
+            }
+        }
+        if flat.len() == 1 {
+            flat.pop().unwrap()
+        } else {
+            StarFreeExpr::Concat(flat)
+        }
+    }
+
+    // flattens nested unions and merges the letters and classes among the
+    // parts into a single class, in place of the first of them
+    pub fn union(parts: Vec<StarFreeExpr>) -> Self {
+        let mut flat = Vec::new();
+        let mut letters = BitSet::with_capacity(256);
+        let mut class_at = None;
+        for part in parts {
+            let inner = match part {
+                StarFreeExpr::Union(inner) => inner,
+                part => vec![part],
+            };
+            for part in inner {
+                match part {
+                    StarFreeExpr::Letter(a) => {
+                        letters.insert(a as usize);
+                    }
+                    StarFreeExpr::Class(ref class) => letters.union_with(class),
+                    part => {
+                        flat.push(part);
+                        continue;
+                    }
+                }
+                if class_at.is_none() {
+                    class_at = Some(flat.len());
+                }
+            }
+        }
+        if let Some(i) = class_at {
+            flat.insert(i, StarFreeExpr::class(letters));
+        }
+        if flat.len() == 1 {
+            flat.pop().unwrap()
+        } else {
+            StarFreeExpr::Union(flat)
+        }
+    }
+
+    pub fn complement(e: StarFreeExpr) -> Self {
+        StarFreeExpr::Complement(Box::new(e))
+    }
+
+    pub fn to_regex(&self) -> RegularExpression {
+        match *self {
+            StarFreeExpr::Empty => RegularExpression::Empty,
+            StarFreeExpr::Letter(a) => RegularExpression::Char(a),
+            StarFreeExpr::Class(ref class) => RegularExpression::Class(class.clone(), false),
+            StarFreeExpr::Concat(ref parts) => {
+                fold_regex(parts, RegularExpression::Epsilon, RegularExpression::Concat)
+            }
+            StarFreeExpr::Union(ref parts) => {
+                fold_regex(parts, RegularExpression::Empty, RegularExpression::Union)
+            }
+            StarFreeExpr::Complement(ref e) => {
+                RegularExpression::Complement(Box::new(e.to_regex()))
+            }
+        }
+    }
+
+    // star-free fragment of the regular expressions; None for stars and repetitions
+    pub fn from_regex(re: &RegularExpression) -> Option<Self> {
+        match *re {
+            RegularExpression::Empty => Some(StarFreeExpr::Empty),
+            RegularExpression::Epsilon => Some(StarFreeExpr::epsilon()),
+            RegularExpression::Char(a) => Some(StarFreeExpr::Letter(a)),
+            RegularExpression::Class(ref class, _) => Some(StarFreeExpr::Class(class.clone())),
+            RegularExpression::Concat(ref e1, ref e2) => Some(StarFreeExpr::concat(vec![
+                StarFreeExpr::from_regex(e1)?,
+                StarFreeExpr::from_regex(e2)?,
+            ])),
+            RegularExpression::Union(ref e1, ref e2) => Some(StarFreeExpr::union(vec![
+                StarFreeExpr::from_regex(e1)?,
+                StarFreeExpr::from_regex(e2)?,
+            ])),
+            // e1 & e2 = !(!e1 | !e2)
+            RegularExpression::Intersection(ref e1, ref e2) => {
+                Some(StarFreeExpr::complement(StarFreeExpr::union(vec![
+                    StarFreeExpr::complement(StarFreeExpr::from_regex(e1)?),
+                    StarFreeExpr::complement(StarFreeExpr::from_regex(e2)?),
+                ])))
+            }
+            RegularExpression::Complement(ref e) => {
+                Some(StarFreeExpr::complement(StarFreeExpr::from_regex(e)?))
+            }
+            RegularExpression::Kleene(_) | RegularExpression::Repeat(_, _, _) => None,
+        }
+    }
+
+    // the minimal dfa of the expression read as a language over `alphabet`
+    pub fn to_dfa(&self, alphabet: &BitSet) -> DFA {
+        DFA::construct_from_nfa(&NFA::construct(&self.to_regex(), alphabet)).minimize()
+    }
+}
+
+// folds an associative operator as a balanced tree, so that unions over large
+// alphabets do not nest deeply
+fn fold_regex(
+    parts: &[StarFreeExpr],
+    unit: RegularExpression,
+    op: fn(Box<RegularExpression>, Box<RegularExpression>) -> RegularExpression,
+) -> RegularExpression {
+    match parts.len() {
+        0 => unit,
+        1 => parts[0].to_regex(),
+        n => {
+            let left = fold_regex(&parts[..n / 2], unit.clone(), op);
+            let right = fold_regex(&parts[n / 2..], unit, op);
+            op(Box::new(left), Box::new(right))
+        }
+    }
+}
+
+fn write_concat_operand(f: &mut fmt::Formatter, e: &StarFreeExpr) -> fmt::Result {
+    match *e {
+        StarFreeExpr::Concat(ref parts) if parts.is_empty() => write!(f, "()"),
+        StarFreeExpr::Concat(_) | StarFreeExpr::Union(_) => write!(f, "({})", e),
+        _ => write!(f, "{}", e),
+    }
+}
+
+impl fmt::Display for StarFreeExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StarFreeExpr::Empty => write!(f, "@"),
+            StarFreeExpr::Letter(a) => write!(f, "{}", escape_char(a)),
+            StarFreeExpr::Class(ref class) => write!(f, "{}", class_to_string(class)),
+            StarFreeExpr::Concat(ref parts) => {
+                for e in parts {
+                    write_concat_operand(f, e)?;
+                }
+                Ok(())
+            }
+            StarFreeExpr::Union(ref parts) => {
+                if parts.is_empty() {
+                    return write!(f, "@");
+                }
+                for (i, e) in parts.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "|")?;
+                    }
+                    match *e {
+                        StarFreeExpr::Union(_) => write!(f, "({})", e)?,
+                        _ => write!(f, "{}", e)?,
+                    }
+                }
+                Ok(())
+            }
+            StarFreeExpr::Complement(ref e) => match **e {
+                StarFreeExpr::Concat(_) | StarFreeExpr::Union(_) => write!(f, "!({})", e),
+                _ => write!(f, "!{}", e),
+            },
+        }
+    }
+}
+
+pub struct StarFreeParser {
+    parser: Parser,
+}
+
+impl Default for StarFreeParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StarFreeParser {
+    pub fn new() -> Self {
+        StarFreeParser {
+            parser: Parser::new(),
+        }
+    }
+    pub fn parse(&mut self, text: &str) -> Result<StarFreeExpr, ParseError> {
+        self.parser.reset(text);
+        let exp = self.read_union()?;
+        match self.parser.peek() {
+            None => Ok(exp),
+            Some(')') => Err(self.parser.error(
+                ParseErrorKind::UnmatchedParen,
+                vec!["'|'", "end of pattern"],
+            )),
+            Some(_) => Err(self
+                .parser
+                .error(ParseErrorKind::TrailingInput, vec!["end of pattern"])),
+        }
+    }
+
+    fn read_union(&mut self) -> Result<StarFreeExpr, ParseError> {
+        let mut parts = vec![self.read_concat()?];
+        while self.parser.peek() == Some('|') {
+            self.parser.bump();
+            parts.push(self.read_concat()?);
+        }
+        Ok(StarFreeExpr::union(parts))
+    }
+
+    fn read_concat(&mut self) -> Result<StarFreeExpr, ParseError> {
+        let mut parts = Vec::new();
+        while self.parser.peek().is_some()
+            && self.parser.peek() != Some(')')
+            && self.parser.peek() != Some('|')
+        {
+            parts.push(self.read_complement()?);
+        }
+        Ok(StarFreeExpr::concat(parts))
+    }
+
+    fn read_complement(&mut self) -> Result<StarFreeExpr, ParseError> {
+        if self.parser.peek() == Some('!') {
+            self.parser.bump();
+            let exp = self.read_complement()?;
+            Ok(StarFreeExpr::complement(exp))
+        } else {
+            self.read_atom()
+        }
+    }
+
+    fn read_atom(&mut self) -> Result<StarFreeExpr, ParseError> {
+        match self.parser.peek() {
+            Some('(') => {
+                let open = self.parser.position();
+                self.parser.bump();
+                let exp = self.read_union()?;
+                if self.parser.peek() != Some(')') {
+                    return Err(self.parser.error_at(
+                        open,
+                        ParseErrorKind::UnterminatedGroup,
+                        vec!["')'"],
+                    ));
+                }
+                self.parser.bump();
+                Ok(exp)
+            }
+            Some('*') | Some('+') | Some('?') | Some('{') | Some('&') => Err(self
+                .parser
+                .error(ParseErrorKind::NotStarFree, vec!["character", "'('", "'!'"])),
+            _ => {
+                let re = self.parser.read_factor()?;
+                Ok(StarFreeExpr::from_regex(&re).unwrap())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntactic_monoid::tests::{minimal_dfa, monoid};
+
+    #[test]
+    fn expression_recognizes_the_language() {
+        for pattern in &[
+            "(ab)*",
+            "a*b*",
+            "(a|b)*aa(a|b)*",
+            "!((a|b)*aa(a|b)*)",
+            "(a|b)*ab",
+            "b*a(a|b)*",
+            "a*|b*",
+            "((a|b)*b)?",
+            "a(b|c)*a",
+            "ε",
+            "@",
+        ] {
+            let (dfa, sm) = (minimal_dfa(pattern, "abc"), monoid(pattern, "abc"));
+            let exp = sm.starfree_expression().unwrap();
+            assert_eq!(
+                exp.to_dfa(&dfa.alphabet).equivalent(&dfa),
+                Ok(()),
+                "{} gave {}",
+                pattern,
+                exp
+            );
+        }
+    }
+
+    #[test]
+    fn not_star_free() {
+        for pattern in &["(aa)*", "(b*ab*a)*b*"] {
+            let sm = monoid(pattern, "ab");
+            assert_eq!(sm.starfree_expression(), None);
+        }
+    }
+
+    #[test]
+    fn expression_parses_back() {
+        let (dfa, sm) = (minimal_dfa("(ab)*", "ab"), monoid("(ab)*", "ab"));
+        let exp = sm.starfree_expression().unwrap();
+        let parsed = StarFreeParser::new().parse(&exp.to_string()).unwrap();
+        assert_eq!(parsed.to_dfa(&dfa.alphabet).equivalent(&dfa), Ok(()));
+    }
+
+    #[test]
+    fn letters_merge_into_a_class() {
+        let letter = |a: u8| StarFreeExpr::Letter(a);
+        let class: BitSet = b"abc".iter().map(|a| *a as usize).collect();
+        let ab = StarFreeExpr::concat(vec![letter(b'a'), letter(b'b')]);
+        assert_eq!(
+            StarFreeExpr::union(vec![
+                ab.clone(),
+                letter(b'a'),
+                StarFreeExpr::union(vec![letter(b'c'), letter(b'b')]),
+            ]),
+            StarFreeExpr::Union(vec![ab, StarFreeExpr::Class(class)])
+        );
+        assert_eq!(
+            StarFreeExpr::union(vec![letter(b'a'), letter(b'a')]),
+            letter(b'a')
+        );
+        let sm = monoid("!(.*aa.*)", "ab");
+        let exp = sm.starfree_expression().unwrap().to_string();
+        assert!(!exp.contains("a|b") && !exp.contains("b|a"), "{}", exp);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |text: &str| {
+            let e = StarFreeParser::new().parse(text).unwrap_err();
+            (e.kind, e.pos)
+        };
+        assert_eq!(error("a*"), (ParseErrorKind::NotStarFree, 1));
+        assert_eq!(error("!(a"), (ParseErrorKind::UnterminatedGroup, 1));
+        assert_eq!(error("a)"), (ParseErrorKind::UnmatchedParen, 1));
+    }
+}
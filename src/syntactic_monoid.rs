@@ -1,67 +1,136 @@
 use super::dfa::DFA;
-use regex::RegularExpression;
+use bit_set::BitSet;
+use regex::word_to_string;
+use starfree::StarFreeExpr;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::collections::VecDeque;
-use std::collections::BTreeSet;
-
-extern crate bit_set;
-use self::bit_set::BitSet;
+use std::fmt;
 
+// an element as the partial map it induces on the dfa states; -1 is the sink
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
+struct Transformation(Vec<i32>);
+
+impl Transformation {
+    fn ident(size: usize) -> Self {
+        Transformation((0..size as i32).collect())
+    }
+    fn then_letter(&self, dfa: &DFA, c: u8) -> Self {
+        Transformation(
+            self.0
+                .iter()
+                .map(|q| {
+                    if *q == -1 {
+                        -1
+                    } else {
+                        dfa.get_trans(*q as usize, c)
+                    }
+                })
+                .collect(),
+        )
+    }
+    // self followed by other
+    fn then(&self, other: &Transformation) -> Self {
+        Transformation(
+            self.0
+                .iter()
+                .map(|q| if *q == -1 { -1 } else { other.0[*q as usize] })
+                .collect(),
+        )
+    }
+    fn image(&self, q: usize) -> i32 {
+        self.0[q]
+    }
+    fn to_matrix(&self) -> Matrix {
+        let mut mat = Matrix::new(self.0.len());
+        for (i, q) in self.0.iter().enumerate() {
+            if *q != -1 {
+                mat.set(i, *q as usize, 1);
+            }
+        }
+        mat
+    }
+}
+
+impl fmt::Display for Transformation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for q in &self.0 {
+            if *q == -1 {
+                write!(f, " -")?;
+            } else {
+                write!(f, " {}", q)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the boolean matrix of a transformation, only used for rendering
 struct Matrix {
-    pub mat: Vec<Vec<u8>>,
-    n: usize,
+    mat: Vec<Vec<u8>>,
 }
 
 impl Matrix {
     fn new(size: usize) -> Self {
         Matrix {
             mat: vec![vec![0; size]; size],
-            n: size,
         }
     }
-    fn ident(size: usize) -> Self {
-        let mut mat = vec![vec![0; size]; size];
-        for i in 0..size {
-            mat[i][i] = 1;
-        }
-        Matrix { mat: mat, n: size }
-    }
-    fn get(&self, i: usize, j: usize) -> u8 {
-        self.mat[i][j]
-    }
     fn set(&mut self, i: usize, j: usize, a: u8) {
         self.mat[i][j] = a;
     }
-    fn to_string(&self) -> String {
-        let mut s = String::new();
+}
+
+impl fmt::Display for Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for rvec in &self.mat {
             for c in rvec {
-                s = format!("{} {}", s, c);
+                write!(f, " {}", c)?;
             }
-            s = format!("{}\n", s);
-        }
-        s
-    }
-    fn multiply(&self, m: &Matrix) -> Self {
-        let mut mat = vec![vec![0; self.n]; self.n];
-        for i in 0..self.n {
-            for j in 0..self.n {
-                for k in 0..self.n {
-                    mat[i][j] = mat[i][j] + self.mat[i][k] * m.mat[k][j];
-                }
-            }
-        }
-        Matrix {
-            mat: mat,
-            n: self.n,
+            writeln!(f)?;
         }
+        Ok(())
     }
 }
 
-type ElemType = usize;
-type ElemSet = BTreeSet<ElemType>;
+pub type ElemType = usize;
+pub type ElemSet = BTreeSet<ElemType>;
+
+// Proof that a language is not star-free: `u` maps to an element whose powers
+// are eventually periodic with `period` > 1 from `index` on. For every n >=
+// index divisible by the period, exactly one of x u^n y and x u^(n+1) y is in
+// the language, which no star-free language allows.
+#[derive(Debug, Clone)]
+pub struct NonAperiodicWitness {
+    pub element: ElemType,
+    pub index: usize,
+    pub period: usize,
+    pub u: Vec<u8>,
+    pub x: Vec<u8>,
+    pub y: Vec<u8>,
+}
+
+// a row of the power table: the powers of `element` are eventually periodic,
+// and from `index` on they form the cyclic group `cyclic_subgroup` of order
+// `period` whose identity is `omega`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerRow {
+    pub element: ElemType,
+    pub index: usize,
+    pub period: usize,
+    pub omega: ElemType,
+    pub cyclic_subgroup: Vec<ElemType>,
+}
+
+// a letter of a word that is not in the alphabet of the monoid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownLetter(pub u8);
+
+impl fmt::Display for UnknownLetter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is not in the alphabet", word_to_string(&[self.0]))
+    }
+}
 
 pub fn identity(e: &ElemType) -> bool {
     *e == 0
@@ -69,14 +138,23 @@ pub fn identity(e: &ElemType) -> bool {
 
 pub struct SyntacticMonoid {
     multiplication_table: Vec<Vec<ElemType>>,
-    transitions_map: HashMap<Matrix, usize>,
-    transitions: Vec<Matrix>,
-    dfa: DFA,
-    alphabets: BitSet,
+    transitions_map: HashMap<Transformation, usize>,
+    transitions: Vec<Transformation>,
+    alphabet: BitSet,
     accept: Vec<bool>,
     charmorphism: HashMap<u8, ElemType>,
-    deg: usize, // a number of elements
-    input: String,
+    letters: Vec<u8>,                 // the generators, in increasing order
+    right_cayley: Vec<Vec<ElemType>>, // right_cayley[e][i] = e * letters[i]
+    left_cayley: Vec<Vec<ElemType>>,  // left_cayley[e][i] = letters[i] * e
+    rules: Vec<(Vec<u8>, Vec<u8>)>,
+    words: Vec<Vec<u8>>, // the length-lexicographically least word mapped to each element
+    deg: usize,          // a number of elements
+}
+
+impl Default for SyntacticMonoid {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SyntacticMonoid {
@@ -85,29 +163,37 @@ impl SyntacticMonoid {
             multiplication_table: Vec::new(),
             transitions_map: HashMap::new(),
             transitions: Vec::new(),
-            dfa: DFA::new(),
-            alphabets: BitSet::with_capacity(256),
+            alphabet: BitSet::new(),
             accept: Vec::new(),
             charmorphism: HashMap::new(),
+            letters: Vec::new(),
+            right_cayley: Vec::new(),
+            left_cayley: Vec::new(),
+            rules: Vec::new(),
+            words: Vec::new(),
             deg: 0,
-            input: String::new(),
         }
     }
-    pub fn morphism(&self, text: String) -> ElemType {
-        let mut mat = Matrix::new(self.dfa.size());
-        let u8vec: Vec<u8> = text.chars().map(|x| x as u8).collect();
-        for i in 0..self.dfa.size() {
-            let mut state = i as i32;
-            for c in &u8vec {
-                if state != -1 {
-                    state = self.dfa.get_trans(state as usize, *c);
-                }
-            }
-            if state != -1 {
-                mat.set(i, state as usize, 1);
-            }
-        }
-        *self.transitions_map.get(&mat).unwrap()
+    // the element a word over the alphabet is mapped to
+    pub fn morphism(&self, word: &[u8]) -> Result<ElemType, UnknownLetter> {
+        word.iter()
+            .try_fold(0, |e, a| match self.charmorphism.get(a) {
+                Some(f) => Ok(self.multiplication_table[e][*f]),
+                None => Err(UnknownLetter(*a)),
+            })
+    }
+
+    // the shortest context (x, y) such that exactly one of x s y and x t y is
+    // accepted, None iff s = t since the monoid is built from a minimal dfa
+    pub fn separating_context(&self, s: ElemType, t: ElemType) -> Option<(Vec<u8>, Vec<u8>)> {
+        let table = &self.multiplication_table;
+        (0..self.deg)
+            .flat_map(|x| (0..self.deg).map(move |y| (x, y)))
+            .filter(|&(x, y)| {
+                self.accept[table[table[x][s]][y]] != self.accept[table[table[x][t]][y]]
+            })
+            .min_by_key(|&(x, y)| self.words[x].len() + self.words[y].len())
+            .map(|(x, y)| (self.words[x].clone(), self.words[y].clone()))
     }
 
     pub fn make_elemset(&self) -> ElemSet {
@@ -143,127 +229,407 @@ impl SyntacticMonoid {
     }
 
     pub fn aperiodic(&self) -> bool {
-        for i in 0..self.deg {
-            let mut e = i;
-            for _ in 0..self.deg {
-                e = self.multiplication_table[e][i];
+        (0..self.deg).all(|e| self.period(e) == 1)
+    }
+
+    // the least i >= 1 with e^i = e^(i+p) for some p > 0
+    pub fn index(&self, e: ElemType) -> usize {
+        self.index_period(e).0
+    }
+
+    // the least p > 0 with e^i = e^(i+p), where i is the index
+    pub fn period(&self, e: ElemType) -> usize {
+        self.index_period(e).1
+    }
+
+    // the powers e^i, ..., e^(i+p-1) from the index on, a cyclic group of order p
+    // whose identity is the idempotent power of e
+    pub fn cyclic_subgroup(&self, e: ElemType) -> Vec<ElemType> {
+        let (index, period) = self.index_period(e);
+        let mut p = e;
+        for _ in 1..index {
+            p = self.multiplication_table[p][e];
+        }
+        let mut group = Vec::with_capacity(period);
+        for _ in 0..period {
+            group.push(p);
+            p = self.multiplication_table[p][e];
+        }
+        group.sort();
+        group
+    }
+
+    fn index_period(&self, e: ElemType) -> (usize, usize) {
+        let mut powers: HashMap<ElemType, usize> = HashMap::new();
+        let mut p = e;
+        for k in 1.. {
+            if let Some(i) = powers.insert(p, k) {
+                return (i, k - i);
             }
-            if e != self.multiplication_table[e][i] {
-                return false;
+            p = self.multiplication_table[p][e];
+        }
+        unreachable!()
+    }
+
+    pub fn non_aperiodic_witness(&self) -> Option<NonAperiodicWitness> {
+        let e = (0..self.deg).find(|e| {
+            let w = self.omega(*e);
+            self.multiplication_table[w][*e] != w
+        })?;
+        let (index, period) = self.index_period(e);
+        let s = self.omega(e);
+        let t = self.multiplication_table[s][e];
+        // the shortest context separating u^ω from u^(ω+1)
+        let (x, y) = self.separating_context(s, t).unwrap();
+        Some(NonAperiodicWitness {
+            element: e,
+            index,
+            period,
+            u: self.words[e].clone(),
+            x,
+            y,
+        })
+    }
+
+    // the number of elements
+    pub fn size(&self) -> usize {
+        self.deg
+    }
+
+    pub fn multiply(&self, a: ElemType, b: ElemType) -> ElemType {
+        self.multiplication_table[a][b]
+    }
+
+    // the image of a letter of the alphabet
+    pub fn letter(&self, a: u8) -> Result<ElemType, UnknownLetter> {
+        self.charmorphism.get(&a).cloned().ok_or(UnknownLetter(a))
+    }
+
+    pub fn is_idempotent(&self, e: ElemType) -> bool {
+        self.multiplication_table[e][e] == e
+    }
+
+    // e^n by repeated squaring, so that large exponents stay cheap
+    pub fn power(&self, e: ElemType, n: usize) -> ElemType {
+        let (mut result, mut square, mut n) = (0, e, n);
+        while n > 0 {
+            if n & 1 == 1 {
+                result = self.multiplication_table[result][square];
             }
+            square = self.multiplication_table[square][square];
+            n >>= 1;
+        }
+        result
+    }
+
+    // the idempotent power of e
+    pub fn omega(&self, e: ElemType) -> ElemType {
+        let mut p = e;
+        while !self.is_idempotent(p) {
+            p = self.multiplication_table[p][e];
+        }
+        p
+    }
+
+    // the edge of the right Cayley graph from e labelled a, i.e. e * a
+    pub fn right_cayley(&self, e: ElemType, a: u8) -> Result<ElemType, UnknownLetter> {
+        match self.letters.binary_search(&a) {
+            Ok(i) => Ok(self.right_cayley[e][i]),
+            Err(_) => Err(UnknownLetter(a)),
+        }
+    }
+
+    // the edge of the left Cayley graph from e labelled a, i.e. a * e
+    pub fn left_cayley(&self, e: ElemType, a: u8) -> Result<ElemType, UnknownLetter> {
+        match self.letters.binary_search(&a) {
+            Ok(i) => Ok(self.left_cayley[e][i]),
+            Err(_) => Err(UnknownLetter(a)),
         }
-        true
+    }
+
+    // the rewriting rules u -> v found by the enumeration. Every proper factor of
+    // u is a representative word and v is the representative of u, so the rules
+    // form a confluent system whose normal forms are the representative words.
+    pub fn rules(&self) -> &[(Vec<u8>, Vec<u8>)] {
+        &self.rules
+    }
+
+    // the image of each dfa state under e, -1 for the sink
+    pub fn images(&self, e: ElemType) -> &[i32] {
+        &self.transitions[e].0
+    }
+
+    pub fn word(&self, e: ElemType) -> &[u8] {
+        &self.words[e]
+    }
+
+    // an element written as its representative word, e.g. [ba]
+    pub fn label(&self, e: ElemType) -> String {
+        format!("[{}]", word_to_string(&self.words[e]))
+    }
+
+    pub fn alphabet(&self) -> &BitSet {
+        &self.alphabet
     }
 
     pub fn accept(&self, e: &ElemType) -> bool {
         self.accept[*e]
     }
 
-    pub fn construct(&mut self, dfa: &DFA, input: &String) {
-        self.dfa = dfa.clone();
-        self.input = input.clone();
-        let ident = Matrix::ident(dfa.size());
+    // the transition monoid of the dfa, which is the syntactic monoid of its
+    // language only if the dfa is minimal, so it must come from `minimize`
+    pub fn construct(&mut self, dfa: &DFA) {
+        assert!(dfa.is_minimum(), "the dfa must be minimized");
+        let letters: Vec<u8> = dfa.alphabet.iter().map(|c| c as u8).collect();
+        self.enumerate(dfa.size(), letters, |t, a| t.then_letter(dfa, a));
+        self.alphabet = dfa.alphabet.clone();
+        self.accept = self
+            .transitions
+            .iter()
+            .map(|t| t.image(dfa.start) != -1 && dfa.states[t.image(dfa.start) as usize].accept)
+            .collect();
+    }
+
+    // the same monoid enumerated over some of its letters, which must generate
+    // it, so that its words and rules only use those letters
+    pub fn over_letters(&self, letters: &[u8]) -> SyntacticMonoid {
+        let mut sm = SyntacticMonoid::new();
+        let size = self.transitions[0].0.len();
+        sm.enumerate(size, letters.to_vec(), |t, a| {
+            t.then(&self.transitions[self.charmorphism[&a]])
+        });
+        sm.alphabet = letters.iter().map(|a| *a as usize).collect();
+        sm.accept = sm
+            .transitions
+            .iter()
+            .map(|t| self.accept[self.transitions_map[t]])
+            .collect();
+        sm
+    }
+
+    // Enumerates the monoid generated by the letters with the Froidure-Pin
+    // algorithm, where `step` applies a letter to a transformation of `size`
+    // states. Elements are found in the length-lexicographic order of their
+    // representative words and numbered in that order. A product u * a is only
+    // computed by `step` when the suffix of u times a is itself a representative;
+    // otherwise it is read off the Cayley graphs built so far.
+    fn enumerate<F>(&mut self, size: usize, letters: Vec<u8>, step: F)
+    where
+        F: Fn(&Transformation, u8) -> Transformation,
+    {
+        let k = letters.len();
+        self.letters = letters.clone();
+        let ident = Transformation::ident(size);
         self.transitions_map.insert(ident.clone(), 0);
-        let mut queue = VecDeque::new();
-        queue.push_back(ident.clone());
-        println!("dfa size:{}", dfa.size());
-        while !queue.is_empty() {
-            let mat = queue.front().unwrap().clone();
-            for c in 0..256 {
-                let mut next = Matrix::new(dfa.size());
-                for i in 0..dfa.size() {
-                    for j in 0..dfa.size() {
-                        if mat.get(i, j) == 1 && dfa.get_trans(j, c as u8) != -1 {
-                            next.set(i, dfa.get_trans(j, c as u8) as usize, 1);
-                        }
-                    }
-                }
-                if !self.transitions_map.contains_key(&next) {
-                    let idx = self.transitions_map.len();
-                    self.transitions_map.insert(next.clone(), idx);
-                    queue.push_back(next.clone());
+        self.transitions.push(ident);
+        self.words.push(Vec::new());
+
+        // first and last letter (as indices into `letters`) of each representative,
+        // and the elements of the representative without its last or first letter
+        let mut first: Vec<usize> = vec![0];
+        let mut last: Vec<usize> = vec![0];
+        let mut prefix: Vec<ElemType> = vec![0];
+        let mut suffix: Vec<ElemType> = vec![0];
+        // reduced[e][i]: the representative of e followed by letters[i] is a representative
+        let mut reduced: Vec<Vec<bool>> = Vec::new();
+        let mut level = 0; // the first element whose left Cayley edges are unknown
+        let mut u = 0;
+        while u < self.transitions.len() {
+            if self.words[u].len() > self.words[level].len() {
+                self.fill_left_cayley(level, u, &prefix, &last);
+                level = u;
+            }
+            self.right_cayley.push(vec![0; k]);
+            reduced.push(vec![false; k]);
+            for (i, a) in letters.iter().enumerate() {
+                if u != 0 && !reduced[suffix[u]][i] {
+                    // u = b s and s a = r, so u a = b r = (b prefix(r)) last(r)
+                    let r = self.right_cayley[suffix[u]][i];
+                    let b = first[u];
+                    self.right_cayley[u][i] = if r == 0 {
+                        self.right_cayley[0][b]
+                    } else {
+                        self.right_cayley[self.left_cayley[prefix[r]][b]][last[r]]
+                    };
+                    continue;
                 }
-                if mat == ident {
-                    self.charmorphism
-                        .insert(c as u8, *self.transitions_map.get(&next).unwrap());
+                let t = step(&self.transitions[u], *a);
+                let idx = self.transitions.len();
+                let v = *self.transitions_map.entry(t.clone()).or_insert(idx);
+                let mut word = self.words[u].clone();
+                word.push(*a);
+                if v == idx {
+                    self.transitions.push(t);
+                    self.words.push(word);
+                    first.push(if u == 0 { i } else { first[u] });
+                    last.push(i);
+                    prefix.push(u);
+                    suffix.push(if u == 0 {
+                        0
+                    } else {
+                        self.right_cayley[suffix[u]][i]
+                    });
+                    reduced[u][i] = true;
+                } else {
+                    self.rules.push((word, self.words[v].clone()));
                 }
+                self.right_cayley[u][i] = v;
             }
-            queue.pop_front().unwrap();
+            u += 1;
         }
+        let n = self.transitions.len();
+        self.fill_left_cayley(level, n, &prefix, &last);
 
-        for p in &self.transitions_map {
-            println!("mat({}) = \n{}", p.1, p.0.to_string());
+        self.deg = n;
+        for (i, a) in self.letters.iter().enumerate() {
+            self.charmorphism.insert(*a, self.right_cayley[0][i]);
         }
+        // u * v follows the right Cayley graph from u along the representative of v
+        self.multiplication_table = vec![vec![0; n]; n];
+        for u in 0..n {
+            self.multiplication_table[u][0] = u;
+            for v in 1..n {
+                self.multiplication_table[u][v] =
+                    self.right_cayley[self.multiplication_table[u][prefix[v]]][last[v]];
+            }
+        }
+    }
 
-        self.deg = self.transitions_map.len();
-        self.accept.resize(self.deg, false);
-        self.transitions.resize(self.deg, Matrix::new(dfa.size()));
-        self.multiplication_table
-            .resize(self.deg, vec![0; self.deg]);
-        for mat_i in &self.transitions_map {
-            self.transitions[*mat_i.1] = mat_i.0.clone();
-            self.accept[*mat_i.1] = false;
+    // left Cayley edges of the elements from..to, all of the same length, once
+    // the right Cayley edges of every element up to that length are known
+    fn fill_left_cayley(&mut self, from: usize, to: usize, prefix: &[ElemType], last: &[usize]) {
+        for u in from..to {
+            let row = if u == 0 {
+                self.right_cayley[0].clone()
+            } else {
+                (0..self.letters.len())
+                    .map(|i| self.right_cayley[self.left_cayley[prefix[u]][i]][last[u]])
+                    .collect()
+            };
+            self.left_cayley.push(row);
+        }
+    }
 
-            for i in 0..dfa.size() {
-                if mat_i.0.get(dfa.start, i) != 0 && dfa.states[i].accept {
-                    self.accept[*mat_i.1] = true;
+    // index, period and idempotent power of every element
+    pub fn power_table(&self) -> Vec<PowerRow> {
+        (0..self.deg)
+            .map(|e| {
+                let (index, period) = self.index_period(e);
+                PowerRow {
+                    element: e,
+                    index,
+                    period,
+                    omega: self.omega(e),
+                    cyclic_subgroup: self.cyclic_subgroup(e),
                 }
-            }
-            for mat_j in &self.transitions_map {
-                let mult_matij = mat_i.0.multiply(mat_j.0);
-                self.multiplication_table[*mat_i.1][*mat_j.1] =
-                    *self.transitions_map.get(&mult_matij).unwrap();
-                let mult_matji = mat_j.0.multiply(mat_i.0);
-                self.multiplication_table[*mat_j.1][*mat_i.1] =
-                    *self.transitions_map.get(&mult_matji).unwrap();
+            })
+            .collect()
+    }
+
+    // the power table, with the cyclic group generated by the elements of
+    // period greater than 1
+    pub fn print_power_table(&self) {
+        let rows: Vec<[String; 5]> = self
+            .power_table()
+            .iter()
+            .map(|row| {
+                let group = if row.period > 1 {
+                    let labels: Vec<String> =
+                        row.cyclic_subgroup.iter().map(|g| self.label(*g)).collect();
+                    format!("Z_{} = {{{}}}", row.period, labels.join(", "))
+                } else {
+                    String::new()
+                };
+                [
+                    self.label(row.element),
+                    row.index.to_string(),
+                    row.period.to_string(),
+                    self.label(row.omega),
+                    group,
+                ]
+            })
+            .collect();
+        let header = [
+            "element".to_string(),
+            "index".to_string(),
+            "period".to_string(),
+            "omega".to_string(),
+            "cyclic subgroup".to_string(),
+        ];
+        let widths: Vec<usize> = (0..5)
+            .map(|j| {
+                rows.iter()
+                    .chain(Some(&header))
+                    .map(|row| row[j].chars().count())
+                    .max()
+                    .unwrap()
+            })
+            .collect();
+        for row in Some(&header).into_iter().chain(&rows) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, w)| format!("{}{}", cell, " ".repeat(w - cell.chars().count())))
+                .collect();
+            println!("{}", cells.join("  ").trim_end());
+        }
+    }
+
+    // lists the elements with their representative words, as transformations
+    // of the dfa states or, with `matrices`, as boolean matrices
+    pub fn print_elements(&self, matrices: bool) {
+        for e in 0..self.deg {
+            if matrices {
+                println!(
+                    "mat({}) {} = \n{}",
+                    e,
+                    self.label(e),
+                    self.transitions[e].to_matrix()
+                );
+            } else {
+                println!("{} {} ={}", e, self.label(e), self.transitions[e]);
             }
         }
     }
-    pub fn starfree_expression(&self) -> Option<String> {
+
+    pub fn starfree_expression(&self) -> Option<StarFreeExpr> {
         if !self.aperiodic() {
             return None;
         }
-        let mut memo: HashMap<ElemType, String> = HashMap::new();
-        let mut regex_vec = vec!["@".to_string()];
+        let mut memo: HashMap<ElemType, StarFreeExpr> = HashMap::new();
+        let mut exprs = Vec::new();
         for e in 0..self.deg {
             if self.accept(&e) {
-                regex_vec.push(self.starfree_recursion(e, &mut memo));
+                exprs.push(self.starfree_recursion(e, &mut memo));
             }
         }
-        let regex = regex_vec.join("|");
-        Some(regex)
+        Some(StarFreeExpr::union(exprs))
     }
     #[allow(non_snake_case)]
-    fn starfree_recursion(&self, m: ElemType, memo: &mut HashMap<ElemType, String>) -> String {
-        if let Some(ref s) = memo.get(&m) {
-            return s.to_string();
+    fn starfree_recursion(
+        &self,
+        m: ElemType,
+        memo: &mut HashMap<ElemType, StarFreeExpr>,
+    ) -> StarFreeExpr {
+        if let Some(e) = memo.get(&m) {
+            return e.clone();
         }
         let regex = if identity(&m) {
             let mut W = BitSet::with_capacity(256);
-            for c in 0..256u32 {
-                if !identity(&self.morphism((c as u8 as char).to_string())) {
-                    W.insert(c as usize);
+            for c in self.alphabet.iter() {
+                if !identity(&self.charmorphism[&(c as u8)]) {
+                    W.insert(c);
                 }
             }
-            if W.len() == 256 {
-                "".to_string()
-            } else if W.len() == 255 {
-                let mut s = String::new();
-                for c in 0..256 {
-                    if !W.contains(c) {
-                        s = format!("{}*", c as u8 as char);
-                        break;
-                    }
-                }
-                s
+            if W.len() == self.alphabet.len() {
+                StarFreeExpr::epsilon()
             } else {
-                let mut s = String::new();
-                for c in 0..256 {
-                    if !W.contains(c) {
-                        s = format!("{}{}", s, c as u8 as char);
-                    }
-                }
-                format!("[{}]*", s)
+                // (A - W)* = A* - A* W A*
+                StarFreeExpr::complement(StarFreeExpr::concat(vec![
+                    StarFreeExpr::universe(),
+                    StarFreeExpr::class(W),
+                    StarFreeExpr::universe(),
+                ]))
             }
         } else {
             let M = self.make_elemset();
@@ -271,57 +637,58 @@ impl SyntacticMonoid {
             let mM = self.left_multiply(m, &M);
 
             // build U A*
-            let mut tmp: Vec<String> = Vec::new();
+            let mut tmp: Vec<StarFreeExpr> = Vec::new();
             for n in 0..(self.deg) {
-                for a in 0..256u32 {
+                for a in self.alphabet.iter() {
                     if mM.contains(&n) {
                         continue;
                     }
-                    let na =
-                        self.multiplication_table[n][self.morphism((a as u8 as char).to_string())];
+                    let na = self.multiplication_table[n][self.charmorphism[&(a as u8)]];
                     let naM = self.left_multiply(na, &M);
                     if naM != mM {
                         continue;
                     }
-                    tmp.push(self.starfree_recursion(n, memo) + &(a as u8 as char).to_string());
+                    tmp.push(StarFreeExpr::concat(vec![
+                        self.starfree_recursion(n, memo),
+                        StarFreeExpr::Letter(a as u8),
+                    ]));
                 }
             }
             let UA = if tmp.is_empty() {
-                "@".to_string()
-            } else if tmp.len() == 1 {
-                format!("{}!@", tmp[0])
+                StarFreeExpr::Empty
             } else {
-                format!("({})!@", tmp.join("|"))
+                StarFreeExpr::concat(vec![StarFreeExpr::union(tmp), StarFreeExpr::universe()])
             };
 
             // build A* V
-            let mut tmp: Vec<String> = Vec::new();
+            let mut tmp: Vec<StarFreeExpr> = Vec::new();
             for n in 0..(self.deg) {
-                for a in 0..256u32 {
+                for a in self.alphabet.iter() {
                     if Mm.contains(&n) {
                         continue;
                     }
-                    let a_e = self.morphism((a as u8 as char).to_string());
+                    let a_e = self.charmorphism[&(a as u8)];
                     let an = self.multiplication_table[a_e][n];
                     let Man = self.right_multiply(&M, an);
                     if Man != Mm {
                         continue;
                     }
-                    tmp.push((a as u8 as char).to_string() + &self.starfree_recursion(n, memo));
+                    tmp.push(StarFreeExpr::concat(vec![
+                        StarFreeExpr::Letter(a as u8),
+                        self.starfree_recursion(n, memo),
+                    ]));
                 }
             }
             let AV = if tmp.is_empty() {
-                "@".to_string()
-            } else if tmp.len() == 1 {
-                format!("!@{}", tmp[0])
+                StarFreeExpr::Empty
             } else {
-                format!("!@({})", tmp.join("|"))
+                StarFreeExpr::concat(vec![StarFreeExpr::universe(), StarFreeExpr::union(tmp)])
             };
 
             // build A* W A*
             let mut W_: HashSet<u8> = HashSet::new();
-            for a in 0..256u32 {
-                let a_e = self.morphism((a as u8 as char).to_string());
+            for a in self.alphabet.iter() {
+                let a_e = self.charmorphism[&(a as u8)];
                 let aM = self.left_multiply(a_e, &M);
                 let MaM = self.elemset_multiply(&M, &aM);
                 if !MaM.contains(&m) {
@@ -329,12 +696,12 @@ impl SyntacticMonoid {
                 }
             }
 
-            let mut tmp: Vec<String> = Vec::new();
-            for a in 0..256u32 {
-                let a_e = self.morphism((a as u8 as char).to_string());
+            let mut tmp: Vec<StarFreeExpr> = Vec::new();
+            for a in self.alphabet.iter() {
+                let a_e = self.charmorphism[&(a as u8)];
                 let Ma = self.right_multiply(&M, a_e);
-                for b in 0..256u32 {
-                    let b_e = self.morphism((b as u8 as char).to_string());
+                for b in self.alphabet.iter() {
+                    let b_e = self.charmorphism[&(b as u8)];
                     let bM = self.left_multiply(b_e, &M);
                     for n in 0..self.deg {
                         let nbM = self.left_multiply(n, &bM);
@@ -346,48 +713,255 @@ impl SyntacticMonoid {
                         let ManM = self.elemset_multiply(&Ma, &nM);
                         let MnbM = self.elemset_multiply(&M, &nbM);
                         if ManM.contains(&m) && MnbM.contains(&m) {
-                            tmp.push(
-                                (a as u8 as char).to_string() + &self.starfree_recursion(n, memo)
-                                    + &(b as u8 as char).to_string(),
-                            );
+                            tmp.push(StarFreeExpr::concat(vec![
+                                StarFreeExpr::Letter(a as u8),
+                                self.starfree_recursion(n, memo),
+                                StarFreeExpr::Letter(b as u8),
+                            ]));
                         }
                     }
                 }
             }
 
             let AWA = if W_.is_empty() && tmp.is_empty() {
-                "@".to_string()
+                StarFreeExpr::Empty
             } else {
-                let mut s = String::new();
+                let mut parts = Vec::new();
                 if !W_.is_empty() {
-                    if W_.len() == 1 {
-                        for w in W_.iter() {
-                            s = s + &(*w as u8 as char).to_string();
-                        }
-                    } else {
-                        let mut f = BitSet::with_capacity(256);
-                        for i in 0..256 {
-                            f.insert(i);
-                        }
-                        let mut W_bit = BitSet::with_capacity(256);
-                        for w in W_.iter() {
-                            W_bit.insert(*w as usize);
-                        }
-                        for w in W_bit.symmetric_difference(&f) {
-                            s = s + &(w as u8 as char).to_string();
-                        }
-                        s = format!("![{}]", s);
-                    }
-                    if !tmp.is_empty() {
-                        s = s + &"|".to_string();
+                    let mut W_bit = BitSet::with_capacity(256);
+                    for w in W_.iter() {
+                        W_bit.insert(*w as usize);
                     }
+                    parts.push(StarFreeExpr::class(W_bit));
                 }
-                format!("!@({}{})!@", s, tmp.join("|"))
+                parts.extend(tmp);
+                StarFreeExpr::concat(vec![
+                    StarFreeExpr::universe(),
+                    StarFreeExpr::union(parts),
+                    StarFreeExpr::universe(),
+                ])
             };
 
-            format!("!(!({})|!({})|{})", UA, AV, AWA)
+            StarFreeExpr::complement(StarFreeExpr::union(vec![
+                StarFreeExpr::complement(UA),
+                StarFreeExpr::complement(AV),
+                AWA,
+            ]))
         };
-        memo.insert(m, regex.to_string());
+        memo.insert(m, regex.clone());
         regex
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use nfa::NFA;
+    use regex::Parser;
+
+    // the minimal dfa of a pattern over the given letters
+    pub(crate) fn minimal_dfa(pattern: &str, letters: &str) -> DFA {
+        let re = Parser::new().parse(pattern).unwrap();
+        let alphabet = Parser::new().parse_alphabet(letters).unwrap();
+        DFA::construct_from_nfa(&NFA::construct(&re, &alphabet)).minimize()
+    }
+
+    // the syntactic monoid of a pattern over the given letters
+    pub(crate) fn monoid(pattern: &str, letters: &str) -> SyntacticMonoid {
+        let mut sm = SyntacticMonoid::new();
+        sm.construct(&minimal_dfa(pattern, letters));
+        sm
+    }
+
+    // the words over `letters` of length at most n, in length-lexicographic order
+    fn words(letters: &[u8], n: usize) -> Vec<Vec<u8>> {
+        let mut words = vec![Vec::new()];
+        let mut i = 0;
+        while i < words.len() {
+            if words[i].len() < n {
+                for a in letters {
+                    let mut w = words[i].clone();
+                    w.push(*a);
+                    words.push(w);
+                }
+            }
+            i += 1;
+        }
+        words
+    }
+
+    #[test]
+    fn sizes() {
+        for &(pattern, letters, size) in &[
+            ("(a|ba)*", "ab", 6),
+            ("(ab)*", "ab", 6),
+            ("(aa)*", "a", 2),
+            ("a*", "a", 1),
+            ("(a|b)*abb", "ab", 7),
+            ("@", "ab", 1),
+        ] {
+            assert_eq!(monoid(pattern, letters).size(), size, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn representatives() {
+        let sm = monoid("(a|b)*abb", "ab");
+        let labels: Vec<String> = (0..sm.size()).map(|e| sm.label(e)).collect();
+        assert_eq!(
+            labels,
+            vec!["[ε]", "[a]", "[b]", "[ab]", "[bb]", "[abb]", "[bbb]"]
+        );
+        for e in 0..sm.size() {
+            assert_eq!(sm.morphism(sm.word(e)), Ok(e));
+        }
+    }
+
+    #[test]
+    fn multiplication() {
+        for &(pattern, letters) in &[("(a|ba)*", "ab"), ("(a|b)*abb", "ab"), ("(abc)*b", "abc")] {
+            let sm = monoid(pattern, letters);
+            for u in 0..sm.size() {
+                for v in 0..sm.size() {
+                    let uv = [sm.word(u), sm.word(v)].concat();
+                    assert_eq!(sm.morphism(&uv), Ok(sm.multiply(u, v)));
+                }
+                for a in letters.bytes() {
+                    let e = sm.letter(a).unwrap();
+                    assert_eq!(sm.right_cayley(u, a), Ok(sm.multiply(u, e)));
+                    assert_eq!(sm.left_cayley(u, a), Ok(sm.multiply(e, u)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rules_rewrite_to_representatives() {
+        let sm = monoid("(a|ba)*", "ab");
+        for (u, v) in sm.rules() {
+            assert_eq!(sm.morphism(u), sm.morphism(v));
+            assert_eq!(sm.word(sm.morphism(v).unwrap()), &v[..]);
+        }
+        for w in words(b"ab", 5) {
+            let mut w = w;
+            while let Some((i, (u, v))) = sm
+                .rules()
+                .iter()
+                .filter_map(|r| {
+                    w.windows(r.0.len())
+                        .position(|f| f == &r.0[..])
+                        .map(|i| (i, r))
+                })
+                .next()
+            {
+                w.splice(i..i + u.len(), v.iter().cloned());
+            }
+            let e = sm.morphism(&w).unwrap();
+            assert_eq!(sm.word(e), &w[..]);
+        }
+    }
+
+    #[test]
+    fn morphism() {
+        let sm = monoid("(a|ba)*", "ab");
+        assert_eq!(sm.morphism(b""), Ok(0));
+        assert_eq!(sm.morphism(b"abba"), sm.morphism(b"bb"));
+        assert!(sm.accept(&sm.morphism(b"aba").unwrap()));
+        assert_eq!(sm.morphism(b"abc"), Err(UnknownLetter(b'c')));
+        assert_eq!(sm.letter(b'c'), Err(UnknownLetter(b'c')));
+        assert_eq!(sm.right_cayley(0, b'c'), Err(UnknownLetter(b'c')));
+        assert_eq!(sm.left_cayley(0, b'c'), Err(UnknownLetter(b'c')));
+    }
+
+    #[test]
+    #[should_panic(expected = "the dfa must be minimized")]
+    fn construct_needs_a_minimized_dfa() {
+        let re = Parser::new().parse("(a|ba)*").unwrap();
+        let dfa = DFA::construct_from_nfa(&NFA::construct(&re, &re.alphabet()));
+        SyntacticMonoid::new().construct(&dfa);
+    }
+
+    #[test]
+    fn separating_context() {
+        let sm = monoid("(a|ba)*", "ab");
+        for s in 0..sm.size() {
+            for t in 0..sm.size() {
+                match sm.separating_context(s, t) {
+                    None => assert_eq!(s, t),
+                    Some((x, y)) => {
+                        let accept = |e: ElemType| {
+                            sm.accept(&sm.morphism(&[&x[..], sm.word(e), &y[..]].concat()).unwrap())
+                        };
+                        assert_ne!(accept(s), accept(t));
+                    }
+                }
+            }
+        }
+        let (a, b) = (sm.letter(b'a').unwrap(), sm.letter(b'b').unwrap());
+        assert_eq!(sm.separating_context(a, b), Some((Vec::new(), Vec::new())));
+    }
+
+    #[test]
+    fn non_aperiodic_witness() {
+        for &(pattern, letters, u, period) in &[
+            ("(aa)*", "a", "a", 2),
+            ("(aaa)*b", "ab", "a", 3),
+            ("(a|b)*a(bb)*", "ab", "b", 2),
+        ] {
+            let w = monoid(pattern, letters).non_aperiodic_witness().unwrap();
+            assert_eq!((&w.u[..], w.period), (u.as_bytes(), period), "{}", pattern);
+            // checked on the dfa rather than through the monoid
+            let dfa = minimal_dfa(pattern, letters);
+            let accept = |n: usize| {
+                let word = [&w.x[..], &w.u.repeat(n)[..], &w.y[..]].concat();
+                dfa.accept(&word)
+            };
+            let mut n = w.period;
+            while n < w.index {
+                n += w.period;
+            }
+            for n in (n..n + 4 * w.period).step_by(w.period) {
+                assert_ne!(accept(n), accept(n + 1), "{} at n = {}", pattern, n);
+            }
+        }
+        for &(pattern, letters) in &[("(ab)*", "ab"), ("a*b*", "ab"), ("@", "ab")] {
+            assert!(monoid(pattern, letters).non_aperiodic_witness().is_none());
+        }
+    }
+
+    #[test]
+    fn power_table() {
+        let sm = monoid("(aaa)*b", "ab");
+        let a = sm.letter(b'a').unwrap();
+        let aaa = sm.morphism(b"aaa").unwrap();
+        let (aa, b) = (sm.morphism(b"aa").unwrap(), sm.letter(b'b').unwrap());
+        let table = sm.power_table();
+        assert_eq!(table.len(), sm.size());
+        assert_eq!(
+            table[a],
+            PowerRow {
+                element: a,
+                index: 1,
+                period: 3,
+                omega: aaa,
+                cyclic_subgroup: vec![a, aa, aaa],
+            }
+        );
+        assert_eq!(sm.label(aaa), "[aaa]");
+        assert_eq!((sm.index(b), sm.period(b)), (2, 1));
+        assert_eq!(sm.omega(b), sm.morphism(b"bb").unwrap());
+        assert_eq!(sm.cyclic_subgroup(b), vec![sm.omega(b)]);
+        for row in &table {
+            let e = row.element;
+            assert_eq!((row.index, row.period), (sm.index(e), sm.period(e)));
+            // e^i = e^(i+p) for the least such i and p
+            assert_eq!(sm.power(e, row.index), sm.power(e, row.index + row.period));
+            assert!((1..row.index)
+                .all(|i| (1..=sm.size()).all(|p| sm.power(e, i) != sm.power(e, i + p))));
+            assert!((1..row.period).all(|p| sm.power(e, row.index) != sm.power(e, row.index + p)));
+            assert!(sm.is_idempotent(row.omega));
+            assert!(row.cyclic_subgroup.contains(&row.omega));
+            assert_eq!(row.cyclic_subgroup.len(), row.period);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use green::GreenRelations;
+use syntactic_monoid::{ElemSet, ElemType, SyntacticMonoid};
+
+// membership of the syntactic monoid in a variety; it is a member iff nothing violates it
+pub struct Membership {
+    pub name: &'static str,
+    pub violators: Vec<ElemType>,
+}
+
+impl Membership {
+    pub fn holds(&self) -> bool {
+        self.violators.is_empty()
+    }
+}
+
+// elements of the syntactic semigroup, the images of the non-empty words
+fn semigroup(monoid: &SyntacticMonoid) -> ElemSet {
+    let mut s = ElemSet::new();
+    for m in 0..monoid.size() {
+        for a in monoid.alphabet().iter() {
+            s.insert(monoid.multiply(m, monoid.letter(a as u8).unwrap()));
+        }
+    }
+    s
+}
+
+// elements lying in a class with more than one element
+fn nontrivial(classes: &[Vec<ElemType>]) -> Vec<ElemType> {
+    let mut violators: Vec<ElemType> = classes
+        .iter()
+        .filter(|c| c.len() > 1)
+        .flat_map(|c| c.iter().cloned())
+        .collect();
+    violators.sort();
+    violators
+}
+
+// the local monoid eSe of an idempotent e
+fn local_monoid(monoid: &SyntacticMonoid, s: &ElemSet, e: ElemType) -> ElemSet {
+    s.iter()
+        .map(|x| monoid.multiply(monoid.multiply(e, *x), e))
+        .collect()
+}
+
+fn is_semilattice(monoid: &SyntacticMonoid, s: &ElemSet) -> bool {
+    s.iter().all(|x| {
+        monoid.is_idempotent(*x)
+            && s.iter()
+                .all(|y| monoid.multiply(*x, *y) == monoid.multiply(*y, *x))
+    })
+}
+
+pub fn report(monoid: &SyntacticMonoid) -> Vec<Membership> {
+    let n = monoid.size();
+    let green = GreenRelations::new(monoid);
+    let elements = || 0..n;
+    let s = semigroup(monoid);
+    let s_idempotents: Vec<ElemType> = s
+        .iter()
+        .cloned()
+        .filter(|e| monoid.is_idempotent(*e))
+        .collect();
+    let d_classes: Vec<Vec<ElemType>> =
+        green.d_classes.iter().map(|d| d.elements.clone()).collect();
+
+    // a zero of the semigroup, if any
+    let zero = s.iter().cloned().find(|z| {
+        s.iter()
+            .all(|x| monoid.multiply(*z, *x) == *z && monoid.multiply(*x, *z) == *z)
+    });
+
+    vec![
+        Membership {
+            name: "aperiodic (star-free)",
+            violators: elements()
+                .filter(|x| {
+                    let e = monoid.omega(*x);
+                    monoid.multiply(e, *x) != e
+                })
+                .collect(),
+        },
+        Membership {
+            name: "J-trivial (piecewise testable)",
+            violators: nontrivial(&d_classes),
+        },
+        Membership {
+            name: "R-trivial",
+            violators: nontrivial(&green.r_classes),
+        },
+        Membership {
+            name: "L-trivial",
+            violators: nontrivial(&green.l_classes),
+        },
+        // every regular D-class consists of idempotents
+        Membership {
+            name: "DA",
+            violators: elements()
+                .filter(|x| {
+                    green.d_classes[green.d_class_of(*x)].regular && !monoid.is_idempotent(*x)
+                })
+                .collect(),
+        },
+        Membership {
+            name: "commutative",
+            violators: elements()
+                .filter(|x| elements().any(|y| monoid.multiply(*x, y) != monoid.multiply(y, *x)))
+                .collect(),
+        },
+        Membership {
+            name: "band (idempotent)",
+            violators: elements().filter(|x| !monoid.is_idempotent(*x)).collect(),
+        },
+        // every element is a unit
+        Membership {
+            name: "group",
+            violators: elements()
+                .filter(|x| elements().all(|y| monoid.multiply(*x, y) != 0))
+                .collect(),
+        },
+        // on the syntactic semigroup: the only idempotent is a zero
+        Membership {
+            name: "nilpotent (finite or cofinite)",
+            violators: s_idempotents
+                .iter()
+                .cloned()
+                .filter(|e| Some(*e) != zero)
+                .collect(),
+        },
+        // on the syntactic semigroup: eSe = {e} for every idempotent e
+        Membership {
+            name: "LI (generalized definite)",
+            violators: s_idempotents
+                .iter()
+                .cloned()
+                .filter(|e| local_monoid(monoid, &s, *e).len() > 1)
+                .collect(),
+        },
+        // on the syntactic semigroup: every eSe is idempotent and commutative
+        Membership {
+            name: "LJ1 (locally testable)",
+            violators: s_idempotents
+                .iter()
+                .cloned()
+                .filter(|e| !is_semilattice(monoid, &local_monoid(monoid, &s, *e)))
+                .collect(),
+        },
+    ]
+}
+
+pub fn print_report(monoid: &SyntacticMonoid) {
+    for membership in report(monoid) {
+        if membership.holds() {
+            println!("  {}: yes", membership.name);
+        } else {
+            let violators: Vec<String> = membership
+                .violators
+                .iter()
+                .map(|e| monoid.label(*e))
+                .collect();
+            println!(
+                "  {}: no, violated by {}",
+                membership.name,
+                violators.join(" ")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntactic_monoid::tests::monoid;
+
+    // the names of the varieties containing the syntactic monoid
+    fn varieties(pattern: &str, letters: &str) -> Vec<&'static str> {
+        report(&monoid(pattern, letters))
+            .into_iter()
+            .filter(|m| m.holds())
+            .map(|m| m.name)
+            .collect()
+    }
+
+    #[test]
+    fn piecewise_testable() {
+        let v = varieties("a*b*", "ab");
+        assert!(v.contains(&"J-trivial (piecewise testable)"));
+        assert!(v.contains(&"R-trivial"));
+        assert!(v.contains(&"L-trivial"));
+        assert!(v.contains(&"aperiodic (star-free)"));
+        assert!(!v.contains(&"commutative"));
+    }
+
+    #[test]
+    fn last_letter() {
+        let v = varieties("(a|b)*a", "ab");
+        assert!(v.contains(&"L-trivial"));
+        assert!(!v.contains(&"R-trivial"));
+        assert!(!v.contains(&"J-trivial (piecewise testable)"));
+        assert!(v.contains(&"DA"));
+        assert!(v.contains(&"band (idempotent)"));
+    }
+
+    #[test]
+    fn not_in_da() {
+        let v = varieties("(a|ba)*", "ab");
+        assert!(!v.contains(&"DA"));
+        assert!(v.contains(&"aperiodic (star-free)"));
+    }
+
+    #[test]
+    fn finite_language() {
+        let v = varieties("ab", "ab");
+        assert!(v.contains(&"nilpotent (finite or cofinite)"));
+        assert!(v.contains(&"LI (generalized definite)"));
+        assert!(!v.contains(&"group"));
+        assert!(!varieties("a*b", "ab").contains(&"nilpotent (finite or cofinite)"));
+    }
+
+    #[test]
+    fn group() {
+        let v = varieties("(aa)*", "a");
+        assert!(v.contains(&"group"));
+        assert!(v.contains(&"commutative"));
+        assert!(!v.contains(&"aperiodic (star-free)"));
+        // the violators of aperiodicity are the elements off their idempotent power
+        let m = report(&monoid("(aa)*", "a"))
+            .into_iter()
+            .find(|m| m.name == "aperiodic (star-free)")
+            .unwrap();
+        assert_eq!(m.violators.len(), 1);
+    }
+}